@@ -1,28 +1,112 @@
-use std::sync::Mutex;
+use std::io::{self, Error, ErrorKind};
+use std::net::SocketAddr;
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex};
 
-use actix_web::{web::Data, App, HttpServer};
+use actix_web::{web, web::Data, App, HttpServer};
+use clap::Parser;
 use healthcheckify::{
-    config::config::AppConfig,
-    healthcheck::health_checker::HealthChecker,
-    http::handler::{home, service_status},
+    cli::{
+        args::{Cli, Command},
+        commands,
+    },
+    config::{config::AppConfig, reload::Reloader},
+    grpc,
+    healthcheck::{health_checker::HealthChecker, scheduler::Scheduler},
+    http::handler::{config_status, heartbeat, heartbeat_signal, home, metrics, service_status},
+    logger::log::{log, log_to_stderr, set_log_level, LogLevel},
 };
 
 #[actix_web::main]
-async fn main() -> std::io::Result<()> {
-    let config = AppConfig::new();
-    let health_checker = HealthChecker::new(config.config_file);
-    let hc = Data::new(Mutex::new(health_checker));
+async fn main() -> io::Result<ExitCode> {
+    let cli = Cli::parse();
+    if let Some(log_level) = &cli.overrides.log_level {
+        set_log_level(log_level);
+    }
+    let config = AppConfig::new(&cli.overrides)?;
+    let command = cli.command.unwrap_or(Command::Serve);
+    if command != Command::Serve {
+        log_to_stderr();
+    }
+    let (mut out, mut err) = (io::stdout(), io::stderr());
+
+    match command {
+        Command::Serve => serve(config).await.map(|_| ExitCode::SUCCESS),
+        Command::Check(args) => Ok(commands::check(&config, &args, &mut out, &mut err).await),
+        Command::Validate => Ok(commands::validate(&config, &mut out, &mut err)),
+        Command::List => Ok(commands::list(&config, &mut out, &mut err)),
+    }
+}
+
+async fn serve(config: AppConfig) -> io::Result<()> {
+    let health_checker = HealthChecker::load(
+        &config.config_file,
+        config.config_format,
+        &config.metrics_path,
+    )
+    .map_err(|err| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("Invalid config file:\n{}", err),
+        )
+    })?;
+    let report = health_checker.report();
+    println!(
+        "Health checker loaded with {} nodes in {} groups",
+        report
+            .iter()
+            .map(|group| group.services.len())
+            .sum::<usize>(),
+        report.len()
+    );
+    let metrics_path = config.metrics_path;
+
+    let health_checker = Arc::new(Mutex::new(health_checker));
+
+    if let Some(grpc_addr) = config.grpc_addr {
+        let grpc_addr: SocketAddr = grpc_addr.parse().map_err(|err| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid gRPC address '{}': {}", grpc_addr, err),
+            )
+        })?;
+        let listener = tokio::net::TcpListener::bind(grpc_addr)
+            .await
+            .map_err(|err| {
+                Error::new(
+                    err.kind(),
+                    format!("Cannot bind gRPC address '{}': {}", grpc_addr, err),
+                )
+            })?;
+        let statuses = health_checker.lock().unwrap().subscribe();
+        tokio::spawn(async move {
+            if let Err(err) = grpc::health::serve(listener, statuses).await {
+                log(
+                    format!("gRPC health server failed: {}", err),
+                    LogLevel::Error,
+                );
+            }
+        });
+    }
+
+    Scheduler::new(Arc::clone(&health_checker)).start();
+    Reloader::new(Arc::clone(&health_checker), config.config_path).start();
+
+    let hc = Data::from(health_checker);
 
     println!("Listening on: {}", config.addr);
 
     HttpServer::new(move || {
-        let app = App::new()
+        App::new()
             .app_data(Data::clone(&hc))
             .service(home)
-            .service(service_status);
-
-        return app;
+            .route(&metrics_path, web::get().to(metrics))
+            .service(config_status)
+            .service(heartbeat)
+            .service(heartbeat_signal)
+            .service(service_status)
     })
+    .workers(config.thread_count)
     .bind(config.addr)?
     .run()
     .await
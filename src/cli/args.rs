@@ -0,0 +1,171 @@
+use std::time::Duration;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use crate::config::config::Overrides;
+
+const ENV_HELP: &str = "\
+Environment variables, also read from .env:
+  BIND_ADDR       Address and port to bind the HTTP server to [default: 127.0.0.1:8080]
+  GRPC_BIND_ADDR  Address and port to serve the gRPC health protocol on [default: disabled]
+  LOG_LEVEL       Log level: debug, info, warn or error [default: error]
+  METRICS_PATH    Path of the Prometheus metrics endpoint [default: /metrics]
+  THREAD_COUNT    Number of worker threads serving HTTP requests [default: 5]
+
+Options given on the command line take precedence over these.";
+
+/// Checks the health of services and serves their status over HTTP.
+#[derive(Debug, Parser)]
+#[command(version, after_help = ENV_HELP)]
+pub struct Cli {
+    #[command(flatten)]
+    pub overrides: Overrides,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, PartialEq, Eq, Subcommand)]
+pub enum Command {
+    /// Check the services in the background and serve their status (the
+    /// default)
+    Serve,
+    /// Check every service once, print their status and exit non-zero if any
+    /// required one is down
+    Check(CheckArgs),
+    /// Check the config file for errors without running anything
+    Validate,
+    /// List the ids in the config file and the services under each
+    List,
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Args)]
+pub struct CheckArgs {
+    /// Ids to check [default: all of them]
+    #[arg(value_name = "ID")]
+    pub ids: Vec<String>,
+    /// How to print the results
+    #[arg(long, value_enum, default_value_t)]
+    pub format: OutputFormat,
+    /// Ids that are reported but don't fail the run when down
+    #[arg(long, value_name = "ID", value_delimiter = ',')]
+    pub optional: Vec<String>,
+    /// Keep checking, at each service's interval, until every required id is
+    /// healthy
+    #[arg(long)]
+    pub wait_until_healthy: bool,
+    /// How long to wait for with --wait-until-healthy, e.g. 90s, 5m or 1h
+    /// [default: 5m]
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, requires = "wait_until_healthy")]
+    pub timeout: Option<Duration>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Table,
+    Json,
+}
+
+/// Parses a whole number of seconds, minutes or hours, e.g. `90s`, `5m` or
+/// `1h`. A bare number is taken as seconds.
+pub fn parse_duration(duration: &str) -> Result<Duration, String> {
+    let invalid = || {
+        format!(
+            "invalid duration '{}', expected e.g. 90s, 5m or 1h",
+            duration
+        )
+    };
+    let (count, unit) = match duration.find(|c: char| !c.is_ascii_digit()) {
+        Some(at) => duration.split_at(at),
+        None => (duration, "s"),
+    };
+    let count: u64 = count.parse().map_err(|_| invalid())?;
+    let unit = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        _ => return Err(invalid()),
+    };
+    count
+        .checked_mul(unit)
+        .map(Duration::from_secs)
+        .ok_or_else(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let cli = Cli::try_parse_from(["healthcheckify"]).unwrap();
+        assert_eq!(cli.command, None);
+        assert_eq!(cli.overrides.config, None);
+
+        let cli = Cli::try_parse_from([
+            "healthcheckify",
+            "check",
+            "--config",
+            "deploy.yaml",
+            "-b",
+            "0.0.0.0:9000",
+            "--log-level",
+            "debug",
+            "-t",
+            "2",
+        ])
+        .unwrap();
+        assert_eq!(cli.command, Some(Command::Check(CheckArgs::default())));
+        assert_eq!(cli.overrides.config.as_deref(), Some("deploy.yaml"));
+        assert_eq!(cli.overrides.bind.as_deref(), Some("0.0.0.0:9000"));
+        assert_eq!(cli.overrides.log_level.as_deref(), Some("debug"));
+        assert_eq!(cli.overrides.threads, Some(2));
+
+        assert!(Cli::try_parse_from(["healthcheckify", "--log-level", "trace"]).is_err());
+        assert!(Cli::try_parse_from(["healthcheckify", "--threads", "many"]).is_err());
+        assert!(Cli::try_parse_from(["healthcheckify", "start"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_check() {
+        let cli = Cli::try_parse_from([
+            "healthcheckify",
+            "check",
+            "web",
+            "api",
+            "--format",
+            "json",
+            "--optional",
+            "docs,status",
+            "--wait-until-healthy",
+            "--timeout",
+            "5m",
+        ])
+        .unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::Check(CheckArgs {
+                ids: vec!["web".to_string(), "api".to_string()],
+                format: OutputFormat::Json,
+                optional: vec!["docs".to_string(), "status".to_string()],
+                wait_until_healthy: true,
+                timeout: Some(Duration::from_secs(300)),
+            }))
+        );
+
+        assert!(Cli::try_parse_from(["healthcheckify", "check", "--timeout", "5m"]).is_err());
+        assert!(Cli::try_parse_from(["healthcheckify", "check", "--format", "yaml"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("5m"), Ok(Duration::from_secs(300)));
+        assert_eq!(parse_duration("1h"), Ok(Duration::from_secs(3600)));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("m").is_err());
+        assert!(parse_duration("5 m").is_err());
+        assert!(parse_duration("1d").is_err());
+    }
+}
@@ -0,0 +1,474 @@
+use std::io::Write;
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::{
+    cli::args::{CheckArgs, OutputFormat},
+    config::config::AppConfig,
+    healthcheck::{health_checker::HealthChecker, node::model::NodeStatus, report::GroupReport},
+};
+
+/// Exit code of a run where some required id is down, or isn't healthy by
+/// the deadline when waiting.
+const DOWN: u8 = 1;
+/// Exit code of a run refused because of an invalid config file, or ids
+/// that aren't in it.
+const INVALID_CONFIG: u8 = 2;
+/// How long `check --wait-until-healthy` waits for by default.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(300);
+/// How often due services are looked for while waiting. Each is only checked
+/// again once its interval has elapsed.
+const RECHECK: Duration = Duration::from_secs(1);
+
+/// Loads the config file, or reports what's wrong with it.
+fn load(config: &AppConfig, err: &mut impl Write) -> Result<HealthChecker, ExitCode> {
+    HealthChecker::load(
+        &config.config_file,
+        config.config_format,
+        &config.metrics_path,
+    )
+    .map_err(|errors| {
+        _ = writeln!(
+            err,
+            "Invalid config file {}:\n{}",
+            config.config_path, errors
+        );
+        ExitCode::from(INVALID_CONFIG)
+    })
+}
+
+/// Checks the config file and prints how much it holds.
+pub fn validate(config: &AppConfig, out: &mut impl Write, err: &mut impl Write) -> ExitCode {
+    let health_checker = match load(config, err) {
+        Ok(health_checker) => health_checker,
+        Err(code) => return code,
+    };
+    let report = health_checker.report();
+    let services: usize = report.iter().map(|group| group.services.len()).sum();
+    _ = writeln!(
+        out,
+        "{}: {} ids, {} services",
+        config.config_path,
+        report.len(),
+        services
+    );
+    ExitCode::SUCCESS
+}
+
+/// Prints every id and the services under it, in config order.
+pub fn list(config: &AppConfig, out: &mut impl Write, err: &mut impl Write) -> ExitCode {
+    let health_checker = match load(config, err) {
+        Ok(health_checker) => health_checker,
+        Err(code) => return code,
+    };
+    for group in health_checker.report() {
+        _ = writeln!(out, "{}", group.id);
+        for service in group.services {
+            _ = writeln!(out, "  {}", service.url);
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+/// Checks the selected ids once, or until all the required ones are healthy
+/// with `--wait-until-healthy`, and prints the results. Fails if any
+/// required id is down, or isn't healthy by the deadline when waiting.
+/// Required ids with heartbeat services are refused, as nothing would ping
+/// them during the run.
+pub async fn check(
+    config: &AppConfig,
+    args: &CheckArgs,
+    out: &mut impl Write,
+    err: &mut impl Write,
+) -> ExitCode {
+    let mut health_checker = match load(config, err) {
+        Ok(health_checker) => health_checker,
+        Err(code) => return code,
+    };
+    let known = health_checker.get_node_ids();
+    if let Some(id) = args
+        .ids
+        .iter()
+        .chain(&args.optional)
+        .find(|id| !known.contains(id))
+    {
+        _ = writeln!(err, "Unknown id '{}'", id);
+        return ExitCode::from(INVALID_CONFIG);
+    }
+    let ids = match args.ids.is_empty() {
+        true => known,
+        false => args.ids.clone(),
+    };
+    let required: Vec<&String> = ids
+        .iter()
+        .filter(|id| !args.optional.contains(id))
+        .collect();
+    if let Some(id) = required.iter().find(|id| {
+        health_checker
+            .heartbeats_by_id(id)
+            .is_some_and(|heartbeats| !heartbeats.is_empty())
+    }) {
+        _ = writeln!(
+            err,
+            "'{}' has heartbeat services, which can't be checked once; leave it out or pass it to --optional",
+            id
+        );
+        return ExitCode::from(INVALID_CONFIG);
+    }
+    // a single round only settles whether services are up, waiting settles
+    // for nothing short of healthy
+    let passes = |status: Option<NodeStatus>| match args.wait_until_healthy {
+        true => status == Some(NodeStatus::Healthy),
+        false => matches!(status, Some(NodeStatus::Healthy | NodeStatus::Degraded)),
+    };
+    let deadline = args
+        .wait_until_healthy
+        .then(|| Instant::now() + args.timeout.unwrap_or(DEFAULT_TIMEOUT));
+
+    let failing = loop {
+        match args.ids.is_empty() {
+            true => health_checker.check_all().await,
+            false => _ = health_checker.check_ids(&ids).await,
+        }
+        let failing: Vec<&String> = required
+            .iter()
+            .copied()
+            .filter(|id| !passes(health_checker.status_by_id(id)))
+            .collect();
+        let left = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        match left {
+            Some(left) if !failing.is_empty() && !left.is_zero() => {
+                tokio::time::sleep(left.min(RECHECK)).await
+            }
+            _ => break failing,
+        }
+    };
+
+    let groups: Vec<(GroupReport, bool)> = health_checker
+        .report()
+        .into_iter()
+        .filter(|group| ids.contains(&group.id))
+        .map(|group| {
+            let required = !args.optional.contains(&group.id);
+            (group, required)
+        })
+        .collect();
+    _ = match args.format {
+        OutputFormat::Table => out.write_all(table(&groups).as_bytes()),
+        OutputFormat::Json => {
+            let ids: Vec<CheckedId> = groups
+                .iter()
+                .map(|(report, required)| CheckedId {
+                    required: *required,
+                    report,
+                })
+                .collect();
+            let report = CheckReport {
+                passed: failing.is_empty(),
+                ids,
+            };
+            writeln!(out, "{}", serde_json::to_string_pretty(&report).unwrap())
+        }
+    };
+
+    if failing.is_empty() {
+        return ExitCode::SUCCESS;
+    }
+    let failing: Vec<&str> = failing.iter().map(|id| id.as_str()).collect();
+    if deadline.is_some() {
+        _ = writeln!(err, "Timed out waiting for: {}", failing.join(", "));
+    }
+    ExitCode::from(DOWN)
+}
+
+#[derive(Serialize)]
+struct CheckReport<'a> {
+    /// Whether every required id passed: up, or healthy when waiting.
+    passed: bool,
+    ids: Vec<CheckedId<'a>>,
+}
+
+#[derive(Serialize)]
+struct CheckedId<'a> {
+    required: bool,
+    #[serde(flatten)]
+    report: &'a GroupReport,
+}
+
+/// Lays out one row per service, under the id and status of its group.
+fn table(groups: &[(GroupReport, bool)]) -> String {
+    let mut rows = vec![[
+        "ID".to_string(),
+        "STATUS".to_string(),
+        "SERVICE".to_string(),
+        "RESULT".to_string(),
+        "LATENCY".to_string(),
+        "ERROR".to_string(),
+    ]];
+    for (group, required) in groups {
+        let status = match required {
+            true => group.status.as_str().to_string(),
+            false => format!("{} (optional)", group.status.as_str()),
+        };
+        for (u, service) in group.services.iter().enumerate() {
+            let (id, status) = match u {
+                0 => (group.id.clone(), status.clone()),
+                _ => (String::new(), String::new()),
+            };
+            rows.push([
+                id,
+                status,
+                service.url.clone(),
+                service.status.as_str().to_string(),
+                service
+                    .latency_ms
+                    .map(|latency| format!("{}ms", latency))
+                    .unwrap_or_default(),
+                service
+                    .error
+                    .as_deref()
+                    .and_then(|error| error.lines().next())
+                    .unwrap_or_default()
+                    .to_string(),
+            ]);
+        }
+    }
+
+    let mut widths = [0; 6];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let mut table = String::new();
+    for row in rows {
+        let line: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect();
+        table.push_str(line.join("  ").trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use std::net::TcpListener;
+    use std::thread;
+
+    use super::*;
+    use crate::cli::args::{Cli, Command};
+    use crate::healthcheck::{parser::ConfigFormat, testing::serve};
+    use clap::Parser;
+
+    fn app_config(config_file: String) -> AppConfig {
+        AppConfig {
+            addr: "127.0.0.1:8080".to_string(),
+            thread_count: 1,
+            config_path: "config.json".to_string(),
+            config_file,
+            config_format: ConfigFormat::Json,
+            metrics_path: "/metrics".to_string(),
+            grpc_addr: None,
+        }
+    }
+
+    #[test]
+    fn test_validate_and_list() {
+        let config = app_config(
+            r#"[
+            {"id":"web","services":[{"url":"http://localhost:3000/"},{"url":"http://localhost:3001/"}]},
+            {"id":"backup","services":[{"type":"heartbeat","interval":3600}]}
+        ]"#
+            .to_string(),
+        );
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        assert_eq!(validate(&config, &mut out, &mut err), ExitCode::SUCCESS);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "config.json: 2 ids, 3 services\n"
+        );
+
+        let mut out = Vec::new();
+        assert_eq!(list(&config, &mut out, &mut err), ExitCode::SUCCESS);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "web\n  http://localhost:3000/\n  http://localhost:3001/\nbackup\n  /heartbeat/backup\n"
+        );
+        assert!(err.is_empty());
+
+        let config = app_config(r#"[{"id":"web","services":[{"url":1}]}]"#.to_string());
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        assert_eq!(
+            validate(&config, &mut out, &mut err),
+            ExitCode::from(INVALID_CONFIG)
+        );
+        assert!(out.is_empty());
+        let err = String::from_utf8(err).unwrap();
+        assert!(err.starts_with("Invalid config file config.json:\n[0].services[0].url"));
+    }
+
+    /// Serves `failures` 500 responses, then 200 ones.
+    fn serve_flaky(failures: usize) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for (n, stream) in listener.incoming().enumerate() {
+                let mut stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => continue,
+                };
+                let mut buf = [0u8; 4096];
+                _ = stream.read(&mut buf);
+                let status = match n < failures {
+                    true => "500 Internal Server Error",
+                    false => "200 OK",
+                };
+                _ = write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nConnection: close\r\nContent-Length: 0\r\n\r\n",
+                    status
+                );
+            }
+        });
+        format!("http://{}/", addr)
+    }
+
+    fn check_args(args: &[&str]) -> CheckArgs {
+        let args = ["healthcheckify", "check"].iter().chain(args);
+        match Cli::try_parse_from(args).unwrap().command {
+            Some(Command::Check(args)) => args,
+            command => panic!("unexpected command {:?}", command),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_check() {
+        let up = serve("HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 0\r\n\r\n");
+        let down = serve_flaky(usize::MAX);
+        let config = app_config(format!(
+            r#"[
+            {{"id":"up","services":[{{"url":"{}"}}]}},
+            {{"id":"down","services":[{{"url":"{}"}}]}},
+            {{"id":"backup","services":[{{"type":"heartbeat","interval":3600}}]}}
+            ]"#,
+            up, down
+        ));
+
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let args = check_args(&["up", "backup", "--optional", "backup"]);
+        let code = check(&config, &args, &mut out, &mut err).await;
+        assert_eq!(code, ExitCode::SUCCESS);
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 3);
+        let words = |line: &str| {
+            line.split_whitespace()
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(words(lines[0])[..4], ["ID", "STATUS", "SERVICE", "RESULT"]);
+        assert_eq!(words(lines[1])[..4], ["up", "healthy", &up, "healthy"]);
+        assert_eq!(
+            words(lines[2]),
+            [
+                "backup",
+                "processing",
+                "(optional)",
+                "/heartbeat/backup",
+                "processing"
+            ]
+        );
+
+        let mut out = Vec::new();
+        let code = check(
+            &config,
+            &check_args(&["--optional", "backup"]),
+            &mut out,
+            &mut err,
+        )
+        .await;
+        assert_eq!(code, ExitCode::from(DOWN));
+        assert!(String::from_utf8(out).unwrap().contains("down    down"));
+
+        let mut out = Vec::new();
+        let args = check_args(&["--optional", "down,backup", "--format", "json"]);
+        let code = check(&config, &args, &mut out, &mut err).await;
+        assert_eq!(code, ExitCode::SUCCESS);
+        let report: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(report["passed"], true);
+        assert_eq!(report["ids"][1]["id"], "down");
+        assert_eq!(report["ids"][1]["required"], false);
+        assert_eq!(report["ids"][1]["status"], "down");
+        assert_eq!(report["ids"][1]["services"][0]["http_code"], 500);
+        assert!(err.is_empty());
+
+        let mut out = Vec::new();
+        let code = check(&config, &check_args(&["missing"]), &mut out, &mut err).await;
+        assert_eq!(code, ExitCode::from(INVALID_CONFIG));
+        assert!(out.is_empty());
+        assert_eq!(String::from_utf8(err).unwrap(), "Unknown id 'missing'\n");
+
+        let mut err = Vec::new();
+        let code = check(&config, &check_args(&[]), &mut out, &mut err).await;
+        assert_eq!(code, ExitCode::from(INVALID_CONFIG));
+        assert!(out.is_empty());
+        assert!(String::from_utf8(err)
+            .unwrap()
+            .starts_with("'backup' has heartbeat services"));
+    }
+
+    #[tokio::test]
+    async fn test_wait_until_healthy() {
+        let flaky = serve_flaky(1);
+        let up = serve_flaky(0);
+        let down = serve_flaky(usize::MAX);
+        let config = app_config(format!(
+            r#"[
+            {{"id":"flaky","services":[{{"url":"{}","interval":1}}]}},
+            {{"id":"down","services":[{{"url":"{}","interval":1}}]}},
+            {{"id":"partial","policy":"any","degraded_on_failure":true,"services":[
+                {{"url":"{}","interval":1}},{{"url":"{}","interval":1}}
+            ]}}
+            ]"#,
+            flaky, down, up, down
+        ));
+
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let args = check_args(&["flaky", "--wait-until-healthy", "--timeout", "10s"]);
+        assert_eq!(
+            check(&config, &args, &mut out, &mut err).await,
+            ExitCode::SUCCESS
+        );
+        assert!(String::from_utf8(out).unwrap().contains("flaky  healthy"));
+        assert!(err.is_empty());
+
+        // degraded is up enough for a single round, not for waiting
+        let mut out = Vec::new();
+        let args = check_args(&["partial"]);
+        assert_eq!(
+            check(&config, &args, &mut out, &mut err).await,
+            ExitCode::SUCCESS
+        );
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .contains("partial  degraded"));
+        assert!(err.is_empty());
+
+        let mut out = Vec::new();
+        let args = check_args(&["--wait-until-healthy", "--timeout", "1s"]);
+        assert_eq!(
+            check(&config, &args, &mut out, &mut err).await,
+            ExitCode::from(DOWN)
+        );
+        assert_eq!(
+            String::from_utf8(err).unwrap(),
+            "Timed out waiting for: down, partial\n"
+        );
+    }
+}
@@ -1,33 +1,142 @@
 use std::env;
 use std::fs::read_to_string;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+use clap::Args;
+
+use crate::{
+    healthcheck::parser::ConfigFormat,
+    http::metrics::{check_path, DEFAULT_METRICS_PATH},
+};
+
+/// Where the config file is looked for, in order.
+const CONFIG_PATHS: [&str; 4] = [
+    "./config.json",
+    "./config.yaml",
+    "./config.yml",
+    "./config.toml",
+];
+
+/// Settings given on the command line, which take precedence over the
+/// environment.
+#[derive(Debug, Default, Args)]
+pub struct Overrides {
+    /// Config file, in JSON, YAML or TOML going by its extension [default:
+    /// the first of ./config.json, ./config.yaml, ./config.yml and
+    /// ./config.toml]
+    #[arg(short, long, global = true, value_name = "PATH")]
+    pub config: Option<String>,
+    /// Address and port to bind the HTTP server to, instead of BIND_ADDR
+    #[arg(short, long, global = true, value_name = "ADDR")]
+    pub bind: Option<String>,
+    /// Log level, instead of LOG_LEVEL
+    #[arg(
+        long,
+        global = true,
+        value_name = "LEVEL",
+        value_parser = ["debug", "info", "warn", "error"]
+    )]
+    pub log_level: Option<String>,
+    /// Number of worker threads serving HTTP requests, instead of
+    /// THREAD_COUNT
+    #[arg(short, long, global = true, value_name = "N")]
+    pub threads: Option<usize>,
+}
 
 pub struct AppConfig {
     pub addr: String,
     pub thread_count: usize,
+    /// Path of the config file, watched for changes.
+    pub config_path: String,
     pub config_file: String,
+    pub config_format: ConfigFormat,
+    pub metrics_path: String,
+    /// Where to serve gRPC health, if anywhere.
+    pub grpc_addr: Option<String>,
 }
 
 impl AppConfig {
-    pub fn new() -> Self {
+    /// Reads the settings from the environment and `.env`, unless given in
+    /// `overrides`, and the config file.
+    pub fn new(overrides: &Overrides) -> Result<Self, Error> {
         dotenv::dotenv().ok();
 
-        let config_file = read_to_string("./config.json").expect("Couldn't find the config file.");
-        let addr = env::var("BIND_ADDR").unwrap_or_else(|_| String::from("127.0.0.1:8080"));
-        let thread_count = env::var("THREAD_COUNT")
-            .unwrap_or_else(|_| String::from("5"))
-            .parse::<usize>()
-            .unwrap();
+        let config_path = match &overrides.config {
+            Some(config_path) => config_path.clone(),
+            None => CONFIG_PATHS
+                .iter()
+                .find(|path| Path::new(path).exists())
+                .unwrap_or(&CONFIG_PATHS[0])
+                .to_string(),
+        };
+        let config_file = read_to_string(&config_path).map_err(|err| {
+            Error::new(
+                err.kind(),
+                format!("Couldn't read the config file {}: {}", config_path, err),
+            )
+        })?;
+        let addr = match &overrides.bind {
+            Some(addr) => addr.clone(),
+            None => env::var("BIND_ADDR").unwrap_or_else(|_| String::from("127.0.0.1:8080")),
+        };
+        let thread_count = match overrides.threads {
+            Some(thread_count) => thread_count,
+            None => {
+                let thread_count = env::var("THREAD_COUNT").unwrap_or_else(|_| String::from("5"));
+                thread_count.parse::<usize>().map_err(|_| {
+                    Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("Invalid THREAD_COUNT: {}", thread_count),
+                    )
+                })?
+            }
+        };
+        let metrics_path =
+            env::var("METRICS_PATH").unwrap_or_else(|_| String::from(DEFAULT_METRICS_PATH));
+        check_path(&metrics_path).map_err(|err| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid METRICS_PATH '{}': {}", metrics_path, err),
+            )
+        })?;
+        let grpc_addr = env::var("GRPC_BIND_ADDR").ok();
 
-        Self {
+        Ok(Self {
             addr,
             thread_count,
+            config_format: ConfigFormat::from_path(&config_path),
+            config_path,
             config_file,
-        }
+            metrics_path,
+            grpc_addr,
+        })
     }
 }
 
-impl Default for AppConfig {
-    fn default() -> Self {
-        Self::new()
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overrides() {
+        let path = std::env::temp_dir().join(format!("healthcheckify-{}.yaml", std::process::id()));
+        std::fs::write(&path, "- id: web\n  services: []\n").unwrap();
+        let overrides = Overrides {
+            config: Some(path.to_string_lossy().to_string()),
+            bind: Some("0.0.0.0:9000".to_string()),
+            log_level: None,
+            threads: Some(2),
+        };
+        let config = AppConfig::new(&overrides).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(config.addr, "0.0.0.0:9000");
+        assert_eq!(config.thread_count, 2);
+        assert_eq!(config.config_format, ConfigFormat::Yaml);
+        assert_eq!(config.config_file, "- id: web\n  services: []\n");
+
+        let err = AppConfig::new(&overrides).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert!(err.to_string().starts_with("Couldn't read the config file"));
     }
 }
@@ -0,0 +1,173 @@
+use std::fs;
+use std::future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::task::JoinHandle;
+
+use crate::{
+    healthcheck::{health_checker::HealthChecker, parser::ConfigFormat},
+    logger::log::{log, LogLevel},
+};
+
+/// How often the config file is looked at for changes.
+const POLL: Duration = Duration::from_secs(2);
+
+/// Reloads the config into the running checker whenever its file changes,
+/// or when the process receives SIGHUP.
+pub struct Reloader {
+    health_checker: Arc<Mutex<HealthChecker>>,
+    path: String,
+    /// Modification time of the file as of the last reload.
+    modified: Option<SystemTime>,
+}
+
+impl Reloader {
+    pub fn new(health_checker: Arc<Mutex<HealthChecker>>, path: String) -> Self {
+        let modified = modified(&path);
+
+        Self {
+            health_checker,
+            path,
+            modified,
+        }
+    }
+
+    /// Reloads the config if its file changed since it was last read.
+    /// Returns whether it did.
+    pub fn poll(&mut self) -> bool {
+        let modified = modified(&self.path);
+        if modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+        self.reload();
+        true
+    }
+
+    /// Reads the config file and hands it over to the checker, which keeps
+    /// running the current config if it is rejected.
+    pub fn reload(&self) {
+        let mut health_checker = self.health_checker.lock().unwrap();
+        match fs::read_to_string(&self.path) {
+            Ok(config) => _ = health_checker.reload(&config, ConfigFormat::from_path(&self.path)),
+            Err(err) => health_checker.reject_reload(format!(
+                "Couldn't read the config file {}: {}",
+                self.path, err
+            )),
+        }
+    }
+
+    /// Keeps watching the config file on the current runtime until the task
+    /// is aborted.
+    pub fn start(mut self) -> JoinHandle<()> {
+        log(
+            format!("Watching config file {}", self.path),
+            LogLevel::Info,
+        );
+        tokio::spawn(async move {
+            let mut hangup = signal(SignalKind::hangup());
+            if let Err(err) = &hangup {
+                log(
+                    format!("Couldn't listen for SIGHUP: {}", err),
+                    LogLevel::Error,
+                );
+            }
+            let mut ticker = tokio::time::interval(POLL);
+            loop {
+                let sighup = async {
+                    match hangup.as_mut() {
+                        Ok(hangup) => hangup.recv().await,
+                        Err(_) => future::pending().await,
+                    }
+                };
+                tokio::select! {
+                    _ = ticker.tick() => {
+                        self.poll();
+                    }
+                    _ = sighup => {
+                        log("SIGHUP received, reloading the config".to_string(), LogLevel::Info);
+                        self.modified = modified(&self.path);
+                        self.reload();
+                    }
+                }
+            }
+        })
+    }
+}
+
+fn modified(path: &str) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::healthcheck::node::{heartbeat::Ping, model::NodeStatus};
+
+    /// Writes the config and moves its modification time forward, since
+    /// consecutive writes may otherwise share the same one.
+    fn write(path: &str, config: &str, age: u64) {
+        fs::write(path, config).unwrap();
+        let file = fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(age))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_poll() {
+        let path = std::env::temp_dir()
+            .join(format!("healthcheckify-reload-{}.json", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        let config = r#"[
+            {"id":"backup","services":[{"type":"heartbeat","interval":3600}]},
+            {"id":"report","services":[{"type":"heartbeat","interval":3600}]}
+        ]"#;
+        write(&path, config, 1);
+        let checker = Arc::new(Mutex::new(HealthChecker::new(config.to_string()).unwrap()));
+        checker.lock().unwrap().ping("backup", Ping::Success, None);
+        checker.lock().unwrap().ping("report", Ping::Success, None);
+        let mut reloader = Reloader::new(Arc::clone(&checker), path.clone());
+        assert!(!reloader.poll());
+
+        write(
+            &path,
+            r#"[
+            {"id":"backup","services":[{"type":"heartbeat","interval":3600}]},
+            {"id":"report","services":[{"type":"heartbeat","interval":7200}]},
+            {"id":"cleanup","services":[{"type":"heartbeat","interval":3600}]}
+        ]"#,
+            2,
+        );
+        assert!(reloader.poll());
+        {
+            let checker = checker.lock().unwrap();
+            assert_eq!(checker.status_by_id("backup"), Some(NodeStatus::Healthy));
+            assert_eq!(checker.status_by_id("report"), Some(NodeStatus::Processing));
+            assert_eq!(
+                checker.status_by_id("cleanup"),
+                Some(NodeStatus::Processing)
+            );
+            assert!(checker.reload_report().error.is_none());
+        }
+
+        write(&path, r#"[{"id":"backup","services":[{"typ":"http"}]}]"#, 3);
+        assert!(reloader.poll());
+        assert!(!reloader.poll());
+        {
+            let checker = checker.lock().unwrap();
+            assert_eq!(checker.get_node_ids(), ["backup", "report", "cleanup"]);
+            let error = checker.reload_report().error.unwrap();
+            assert!(error.starts_with("[0].services[0].typ: unknown field"));
+        }
+
+        fs::remove_file(&path).unwrap();
+        assert!(reloader.poll());
+        let error = checker.lock().unwrap().reload_report().error.unwrap();
+        assert!(error.starts_with("Couldn't read the config file"));
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::transport::Server;
+use tonic_health::pb::health_server::{Health, HealthServer};
+use tonic_health::server::{health_reporter, HealthReporter};
+use tonic_health::ServingStatus;
+
+use crate::healthcheck::node::model::NodeStatus;
+use crate::logger::log::{log, LogLevel};
+
+/// Serves `grpc.health.v1.Health` on `listener`, with one gRPC service per
+/// node id following `statuses` (see `HealthChecker::subscribe`). The
+/// listener is bound by the caller, so that a taken address fails startup.
+pub async fn serve(
+    listener: TcpListener,
+    statuses: watch::Receiver<Vec<(String, NodeStatus)>>,
+) -> Result<(), tonic::transport::Error> {
+    if let Ok(addr) = listener.local_addr() {
+        log(format!("Serving gRPC health on: {}", addr), LogLevel::Info);
+    }
+    Server::builder()
+        .add_service(service(statuses).await)
+        .serve_with_incoming(TcpListenerStream::new(listener))
+        .await
+}
+
+/// The health service, kept up to date with `statuses` for as long as the
+/// sender lives. Ids are served under their own name; the empty service name
+/// stands for healthcheckify as a whole (see `overall_status`).
+pub async fn service(
+    mut statuses: watch::Receiver<Vec<(String, NodeStatus)>>,
+) -> HealthServer<impl Health> {
+    let (mut reporter, service) = health_reporter();
+    // the reporter starts out with the empty name serving
+    let mut served: HashMap<String, ServingStatus> =
+        HashMap::from([(String::new(), ServingStatus::Serving)]);
+    // the current statuses are in place before the service answers anything
+    let current = statuses.borrow_and_update().clone();
+    update(&current, &mut served, &mut reporter).await;
+    tokio::spawn(async move {
+        while statuses.changed().await.is_ok() {
+            let current = statuses.borrow_and_update().clone();
+            update(&current, &mut served, &mut reporter).await;
+        }
+    });
+    service
+}
+
+/// Passes on the statuses that changed since `served`; every update reaches
+/// the watchers.
+async fn update(
+    current: &[(String, NodeStatus)],
+    served: &mut HashMap<String, ServingStatus>,
+    reporter: &mut HealthReporter,
+) {
+    for (id, status) in current.iter() {
+        let status = serving_status(*status);
+        if served.get(id) != Some(&status) {
+            reporter.set_service_status(id, status).await;
+            served.insert(id.clone(), status);
+        }
+    }
+    let removed: Vec<String> = served
+        .keys()
+        .filter(|id| !id.is_empty() && !current.iter().any(|(current_id, _)| current_id == *id))
+        .cloned()
+        .collect();
+    for id in removed {
+        reporter.clear_service_status(&id).await;
+        served.remove(&id);
+    }
+
+    // the overall status goes last, so it never runs ahead of the ids
+    let overall = overall_status(current);
+    if served.get("") != Some(&overall) {
+        reporter.set_service_status("", overall).await;
+        served.insert(String::new(), overall);
+    }
+}
+
+/// `NOT_SERVING` while any id is down, and unknown until at least one id
+/// has been checked.
+fn overall_status(statuses: &[(String, NodeStatus)]) -> ServingStatus {
+    if statuses
+        .iter()
+        .any(|(_, status)| *status == NodeStatus::Down)
+    {
+        ServingStatus::NotServing
+    } else if !statuses.is_empty()
+        && statuses
+            .iter()
+            .all(|(_, status)| *status == NodeStatus::Processing)
+    {
+        ServingStatus::Unknown
+    } else {
+        ServingStatus::Serving
+    }
+}
+
+/// Degraded ids still serve; ids that haven't been checked yet are unknown.
+fn serving_status(status: NodeStatus) -> ServingStatus {
+    match status {
+        NodeStatus::Healthy | NodeStatus::Degraded => ServingStatus::Serving,
+        NodeStatus::Processing => ServingStatus::Unknown,
+        NodeStatus::Down => ServingStatus::NotServing,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::healthcheck::{health_checker::HealthChecker, testing::serve};
+    use tonic::transport::Endpoint;
+    use tonic_health::pb::health_check_response::ServingStatus as Status;
+    use tonic_health::pb::{health_client::HealthClient, HealthCheckRequest};
+
+    fn request(service: &str) -> HealthCheckRequest {
+        HealthCheckRequest {
+            service: service.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_check_and_watch() {
+        let up = serve("HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 2\r\n\r\nok");
+        let data = format!(
+            r#"[
+            {{"id":"up","services":[{{"url":"{}"}}]}},
+            {{"id":"refused","services":[{{"url":"http://127.0.0.1:1/"}}]}}
+            ]"#,
+            up
+        );
+        let mut checker = HealthChecker::new(data).unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = format!("http://{}", listener.local_addr().unwrap());
+        let health = service(checker.subscribe()).await;
+        tokio::spawn(
+            Server::builder()
+                .add_service(health)
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+
+        let channel = Endpoint::from_shared(addr)
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        let mut client = HealthClient::new(channel);
+        let up = client.check(request("up")).await.unwrap();
+        assert_eq!(up.into_inner().status(), Status::Unknown);
+        let mut watch = client.watch(request("")).await.unwrap().into_inner();
+        let first = watch.message().await.unwrap().unwrap();
+        assert_eq!(first.status(), Status::Unknown);
+
+        checker.check_all().await;
+
+        // the results are recorded one by one, "up" may be seen alone first
+        let mut next = watch.message().await.unwrap().unwrap();
+        while next.status() != Status::NotServing {
+            next = watch.message().await.unwrap().unwrap();
+        }
+        let up = client.check(request("up")).await.unwrap();
+        assert_eq!(up.into_inner().status(), Status::Serving);
+        let refused = client.check(request("refused")).await.unwrap();
+        assert_eq!(refused.into_inner().status(), Status::NotServing);
+        assert!(client.check(request("missing")).await.is_err());
+    }
+
+    #[test]
+    fn test_overall_status() {
+        use NodeStatus::{Degraded, Down, Healthy, Processing};
+        let statuses = |statuses: &[NodeStatus]| -> Vec<(String, NodeStatus)> {
+            statuses
+                .iter()
+                .enumerate()
+                .map(|(i, status)| (i.to_string(), *status))
+                .collect()
+        };
+
+        assert_eq!(
+            overall_status(&statuses(&[Processing, Processing])),
+            ServingStatus::Unknown
+        );
+        assert_eq!(
+            overall_status(&statuses(&[Processing, Degraded])),
+            ServingStatus::Serving
+        );
+        assert_eq!(
+            overall_status(&statuses(&[Healthy, Down])),
+            ServingStatus::NotServing
+        );
+        assert_eq!(overall_status(&[]), ServingStatus::Serving);
+    }
+}
@@ -0,0 +1,245 @@
+use std::fmt;
+
+use serde::de::{self, value::MapAccessDeserializer, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+
+use crate::healthcheck::node::model::NodeStatus;
+
+/// How the statuses of a group's services are combined into the status of
+/// the group's id.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum GroupPolicy {
+    /// Healthy only while every service is healthy.
+    #[default]
+    All,
+    /// Healthy while at least one service is healthy.
+    Any,
+    /// Healthy while at least N services are healthy.
+    Quorum(usize),
+    /// Healthy while the weights of the healthy services add up to at least
+    /// the given share of the total weight.
+    Weighted(f64),
+}
+
+impl GroupPolicy {
+    /// Combines the `(status, weight)` of every member service. The group is
+    /// `Degraded` when it needs degraded services to meet the policy, and
+    /// `Processing` while the services that have not reported yet could still
+    /// make it healthy.
+    pub fn evaluate(&self, members: &[(NodeStatus, u32)]) -> NodeStatus {
+        let weight = |&(_, weight): &(NodeStatus, u32)| match self {
+            GroupPolicy::Weighted(_) => weight as f64,
+            _ => 1.0,
+        };
+        let total: f64 = members.iter().map(weight).sum();
+        let sum_of = |status: NodeStatus| -> f64 {
+            members
+                .iter()
+                .filter(|(s, _)| *s == status)
+                .map(weight)
+                .sum()
+        };
+        let healthy = sum_of(NodeStatus::Healthy);
+        let degraded = sum_of(NodeStatus::Degraded);
+        let processing = sum_of(NodeStatus::Processing);
+
+        let required = match self {
+            GroupPolicy::All => total,
+            GroupPolicy::Any => 1.0,
+            GroupPolicy::Quorum(n) => *n as f64,
+            GroupPolicy::Weighted(share) => share * total,
+        };
+
+        if healthy >= required {
+            NodeStatus::Healthy
+        } else if healthy + degraded >= required {
+            NodeStatus::Degraded
+        } else if healthy + degraded + processing >= required {
+            NodeStatus::Processing
+        } else {
+            NodeStatus::Down
+        }
+    }
+}
+
+/// The `policy` field of a group: `"all"` or `"any"`, regardless of case,
+/// `{"quorum": N}` or `{"weighted": share}`.
+impl<'de> Deserialize<'de> for GroupPolicy {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(PolicyVisitor)
+    }
+}
+
+struct PolicyVisitor;
+
+/// The map form of a policy, naming exactly one of its fields.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PolicyMap {
+    quorum: Option<usize>,
+    weighted: Option<f64>,
+}
+
+impl<'de> Visitor<'de> for PolicyVisitor {
+    type Value = GroupPolicy;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(r#""all", "any", {"quorum": N} or {"weighted": share}"#)
+    }
+
+    fn visit_str<E: de::Error>(self, name: &str) -> Result<GroupPolicy, E> {
+        match name.to_ascii_lowercase().as_str() {
+            "all" => Ok(GroupPolicy::All),
+            "any" => Ok(GroupPolicy::Any),
+            _ => Err(E::unknown_variant(name, &["all", "any"])),
+        }
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<GroupPolicy, A::Error> {
+        let policy = PolicyMap::deserialize(MapAccessDeserializer::new(map))?;
+        match (policy.quorum, policy.weighted) {
+            (Some(0), None) => Err(de::Error::invalid_value(
+                de::Unexpected::Unsigned(0),
+                &"a quorum of at least 1",
+            )),
+            (Some(quorum), None) => Ok(GroupPolicy::Quorum(quorum)),
+            (None, Some(share)) if share > 0.0 && share <= 1.0 => Ok(GroupPolicy::Weighted(share)),
+            (None, Some(share)) => Err(de::Error::invalid_value(
+                de::Unexpected::Float(share),
+                &"a weighted share above 0 and up to 1",
+            )),
+            _ => Err(de::Error::custom(
+                "expected exactly one of quorum or weighted",
+            )),
+        }
+    }
+}
+
+/// All the services configured under one id.
+pub(crate) struct NodeGroup {
+    pub id: String,
+    pub policy: GroupPolicy,
+    /// Index into the checker's nodes and weight of every member service.
+    pub members: Vec<(usize, u32)>,
+    /// Whether the id is degraded, rather than healthy, while some of its
+    /// services fail but the policy is still met.
+    pub degraded_on_failure: bool,
+    /// HTTP status code answered while the id is degraded.
+    pub degraded_code: u16,
+}
+
+impl NodeGroup {
+    pub fn new(id: String, policy: GroupPolicy) -> Self {
+        Self {
+            id,
+            policy,
+            members: Vec::new(),
+            degraded_on_failure: false,
+            degraded_code: 200,
+        }
+    }
+
+    /// Combines the `(status, weight)` of every member service with the
+    /// group's policy.
+    pub fn evaluate(&self, members: &[(NodeStatus, u32)]) -> NodeStatus {
+        let status = self.policy.evaluate(members);
+        let failing = members
+            .iter()
+            .any(|(status, _)| matches!(status, NodeStatus::Down | NodeStatus::Degraded));
+        if status == NodeStatus::Healthy && self.degraded_on_failure && failing {
+            NodeStatus::Degraded
+        } else {
+            status
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use NodeStatus::{Degraded, Down, Healthy, Processing};
+
+    fn parse(policy: serde_json::Value) -> Result<GroupPolicy, String> {
+        serde_json::from_value(policy).map_err(|err| err.to_string())
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse(json!("any")).unwrap(), GroupPolicy::Any);
+        assert_eq!(parse(json!("ALL")).unwrap(), GroupPolicy::All);
+        assert_eq!(parse(json!({"quorum": 2})).unwrap(), GroupPolicy::Quorum(2));
+        assert_eq!(
+            parse(json!({"weighted": 0.5})).unwrap(),
+            GroupPolicy::Weighted(0.5)
+        );
+        assert_eq!(
+            parse(json!("most")).unwrap_err(),
+            "unknown variant `most`, expected `all` or `any`"
+        );
+        assert_eq!(
+            parse(json!({"quorum": 0})).unwrap_err(),
+            "invalid value: integer `0`, expected a quorum of at least 1"
+        );
+        assert!(parse(json!({"weighted": 2})).is_err());
+        assert_eq!(
+            parse(json!({"quorum": 2, "typo": 1})).unwrap_err(),
+            "unknown field `typo`, expected `quorum` or `weighted`"
+        );
+        assert_eq!(
+            parse(json!({"quorum": 2, "weighted": 0.5})).unwrap_err(),
+            "expected exactly one of quorum or weighted"
+        );
+        assert!(parse(json!(3)).is_err());
+    }
+
+    #[test]
+    fn test_all() {
+        let policy = GroupPolicy::All;
+        assert_eq!(policy.evaluate(&[(Healthy, 1), (Healthy, 1)]), Healthy);
+        assert_eq!(
+            policy.evaluate(&[(Healthy, 1), (Processing, 1)]),
+            Processing
+        );
+        assert_eq!(policy.evaluate(&[(Healthy, 1), (Down, 1)]), Down);
+        assert_eq!(policy.evaluate(&[(Healthy, 1), (Degraded, 1)]), Degraded);
+    }
+
+    #[test]
+    fn test_any() {
+        let policy = GroupPolicy::Any;
+        assert_eq!(policy.evaluate(&[(Down, 1), (Healthy, 1)]), Healthy);
+        assert_eq!(policy.evaluate(&[(Down, 1), (Processing, 1)]), Processing);
+        assert_eq!(policy.evaluate(&[(Down, 1), (Down, 1)]), Down);
+        assert_eq!(policy.evaluate(&[(Degraded, 1), (Healthy, 1)]), Healthy);
+    }
+
+    #[test]
+    fn test_quorum() {
+        let policy = GroupPolicy::Quorum(2);
+        assert_eq!(
+            policy.evaluate(&[(Healthy, 1), (Down, 1), (Healthy, 1)]),
+            Healthy
+        );
+        assert_eq!(policy.evaluate(&[(Healthy, 1), (Down, 1), (Down, 1)]), Down);
+    }
+
+    #[test]
+    fn test_weighted() {
+        let policy = GroupPolicy::Weighted(0.5);
+        assert_eq!(policy.evaluate(&[(Healthy, 3), (Down, 1)]), Healthy);
+        assert_eq!(policy.evaluate(&[(Down, 3), (Healthy, 1)]), Down);
+    }
+
+    #[test]
+    fn test_degraded_on_failure() {
+        let mut group = NodeGroup::new("db".to_string(), GroupPolicy::Any);
+        assert_eq!(group.evaluate(&[(Down, 1), (Healthy, 1)]), Healthy);
+
+        group.degraded_on_failure = true;
+        assert_eq!(group.evaluate(&[(Down, 1), (Healthy, 1)]), Degraded);
+        assert_eq!(group.evaluate(&[(Degraded, 1), (Healthy, 1)]), Degraded);
+        assert_eq!(group.evaluate(&[(Processing, 1), (Healthy, 1)]), Healthy);
+        assert_eq!(group.evaluate(&[(Down, 1), (Down, 1)]), Down);
+    }
+}
@@ -1,80 +1,206 @@
+use std::env;
 use std::error::Error;
+use std::net::{IpAddr, SocketAddr};
+use std::ops::RangeInclusive;
+use std::time::{Duration, SystemTime};
+
+use regex::Regex;
+use tokio::sync::watch;
 
 use crate::healthcheck::{
-    node::model::Node,
+    group::{GroupPolicy, NodeGroup},
+    node::assertion::JsonAssertion,
+    node::heartbeat::Ping,
+    node::history::Thresholds,
+    node::model::{CheckResult, Node, Probe},
     node::model::{NodeCheckStrategy, NodeStatus, RequestMethod},
-    parser::parse_config,
+    node::retry::RetryPolicy,
+    parser::{parse_config, ConfigError, ConfigFormat, ExpectedStatus, MatchMode, Method},
+    parser::{ServiceConfig, ServiceType, StrategyConfig, StrategyKind},
+    probe::database::DatabaseLogin,
+    probe::dns::{self, DnsQuery, RecordType},
+    probe::exec::ExecCommand,
+    probe::grpc::GrpcTarget,
+    probe::redis::{RedisCheck, RedisRole},
+    probe::tls::CertPolicy,
+    report::{GroupReport, ReloadReport, ServiceReport},
 };
+use crate::http::metrics::DEFAULT_METRICS_PATH;
+use crate::logger::log::{log, LogLevel};
 
 use super::node::config::NodeConfig;
 
 pub struct HealthChecker {
     nodes: Vec<Node>,
+    /// The config every node was built from, by node index.
+    sources: Vec<ServiceConfig>,
+    groups: Vec<NodeGroup>,
+    client: reqwest::Client,
+    statuses: watch::Sender<Vec<(String, NodeStatus)>>,
+    loaded_at: SystemTime,
+    /// When and why the latest reload was rejected, if it was.
+    reload_error: Option<(SystemTime, String)>,
+    /// Route serving the metrics, which no id may shadow.
+    metrics_path: String,
 }
 
 impl HealthChecker {
-    pub fn new(json_config: String) -> Self {
-        let node_configs = parse_config(json_config).unwrap();
-
-        let mut nodes: Vec<Node> = Vec::with_capacity(node_configs.len());
-        for config in node_configs.iter() {
-            let id = config["id"].as_str().unwrap();
-            let services = parse_config(config["services"].to_string()).unwrap();
-            for service in services {
-                let timeout = service["interval"].as_u64().unwrap_or(10u64);
-                let node_config =
-                    NodeConfig::new(service["url"].as_str().unwrap().trim().to_string());
-                let strategy = match service["strategy"].as_str().unwrap_or("statuscode") {
-                    "stringcontains" => {
-                        let _contains_string = service["strategy_string"]
-                            .as_str()
-                            .expect("Strategy search string not defined");
-
-                        NodeCheckStrategy::BodyContains(_contains_string.to_string())
-                    }
-                    _ => NodeCheckStrategy::StatusCode, // default strategy
-                };
-                let lowercase_strategy = service["method"]
-                    .as_str()
-                    .unwrap_or("get")
-                    .to_ascii_lowercase();
-
-                let method = match lowercase_strategy.as_str() {
-                    "post" => RequestMethod::POST,
-                    _ => RequestMethod::GET,
-                };
-
-                let request_body = service["requestBody"].as_str().unwrap_or("").to_string();
-                // convert to Option
-                let request_body = if request_body.is_empty() {
-                    None
-                } else {
-                    Some(request_body)
-                };
-
-                let call_timeout = service["call_timeout"].as_u64().unwrap_or(30u64);
-
-                nodes.push(Node::new(
-                    node_config.clone(),
-                    id.to_string(),
-                    strategy,
-                    timeout,
-                    method,
-                    request_body,
-                    call_timeout,
-                ));
+    /// Loads a JSON config. Every problem found in it is reported, each with
+    /// the path of the offending field.
+    pub fn new(json_config: String) -> Result<Self, ConfigError> {
+        Self::load(&json_config, ConfigFormat::Json, DEFAULT_METRICS_PATH)
+    }
+
+    /// Loads a config written in any of the supported formats. Ids may not
+    /// shadow `metrics_path`, on load as on every reload.
+    pub fn load(
+        config: &str,
+        format: ConfigFormat,
+        metrics_path: &str,
+    ) -> Result<Self, ConfigError> {
+        let group_configs = parse_config(config, format)?;
+
+        let mut errors = ConfigError::default();
+        let mut nodes: Vec<Node> = Vec::new();
+        let mut sources: Vec<ServiceConfig> = Vec::new();
+        let mut groups: Vec<NodeGroup> = Vec::with_capacity(group_configs.len());
+        for (i, config) in group_configs.iter().enumerate() {
+            let path = format!("[{}]", i);
+            if config.id.trim().is_empty() {
+                errors.push(&format!("{}.id", path), "must not be empty");
+            }
+            if config.id == metrics_path.trim_start_matches('/') {
+                errors.push(
+                    &format!("{}.id", path),
+                    format!("collides with the metrics path {}", metrics_path),
+                );
+            }
+            if let Some(first) = group_configs[..i]
+                .iter()
+                .position(|group| group.id == config.id)
+            {
+                errors.push(
+                    &format!("{}.id", path),
+                    format!("duplicate of [{}].id", first),
+                );
+                continue;
+            }
+            let policy = config.policy.clone();
+            if let GroupPolicy::Quorum(quorum) = policy {
+                if quorum > config.services.len() {
+                    errors.push(
+                        &format!("{}.policy", path),
+                        format!(
+                            "quorum of {} is more than the {} services",
+                            quorum,
+                            config.services.len()
+                        ),
+                    );
+                }
+            }
+            if !(200..=599).contains(&config.degraded_code) {
+                errors.push(
+                    &format!("{}.degraded_code", path),
+                    "must be between 200 and 599",
+                );
+            }
+            let mut group = NodeGroup::new(config.id.clone(), policy);
+            group.degraded_on_failure = config.degraded_on_failure;
+            group.degraded_code = config.degraded_code;
+            for (j, service) in config.services.iter().enumerate() {
+                let path = format!("{}.services[{}]", path, j);
+                if let Some(node) = build_node(&config.id, service, &path, &mut errors) {
+                    group.members.push((nodes.len(), service.weight));
+                    nodes.push(node);
+                    sources.push(service.clone());
+                }
+            }
+            groups.push(group);
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        let (statuses, _) = watch::channel(Vec::new());
+        let checker = Self {
+            nodes,
+            sources,
+            groups,
+            client: reqwest::Client::new(),
+            statuses,
+            loaded_at: SystemTime::now(),
+            reload_error: None,
+            metrics_path: metrics_path.to_string(),
+        };
+        checker.publish();
+        Ok(checker)
+    }
+
+    /// Replaces the config with a new one. Services whose id and config are
+    /// unchanged keep their status and history, the others start afresh. A
+    /// config with errors is rejected and leaves the current one running;
+    /// the errors are kept for `reload_report`.
+    pub fn reload(&mut self, config: &str, format: ConfigFormat) -> Result<(), ConfigError> {
+        let mut next = match HealthChecker::load(config, format, &self.metrics_path) {
+            Ok(next) => next,
+            Err(err) => {
+                self.reject_reload(err.to_string());
+                return Err(err);
+            }
+        };
+
+        let mut previous: Vec<Option<Node>> = self.nodes.drain(..).map(Some).collect();
+        let mut kept = 0;
+        for (u, source) in next.sources.iter().enumerate() {
+            let unchanged = previous.iter().zip(&self.sources).position(|(node, old)| {
+                node.as_ref()
+                    .is_some_and(|node| node.id == next.nodes[u].id && old == source)
+            });
+            if let Some(v) = unchanged {
+                next.nodes[u] = previous[v].take().unwrap();
+                kept += 1;
             }
         }
+        let removed = previous.iter().flatten().count();
+        log(
+            format!(
+                "Config reloaded: {} services kept, {} added, {} removed",
+                kept,
+                next.nodes.len() - kept,
+                removed
+            ),
+            LogLevel::Info,
+        );
 
-        println!("Health checker loaded with {} nodes", nodes.len());
+        self.nodes = next.nodes;
+        self.sources = next.sources;
+        self.groups = next.groups;
+        self.loaded_at = SystemTime::now();
+        self.reload_error = None;
+        self.publish();
+        Ok(())
+    }
 
-        Self { nodes }
+    /// Keeps the current config running, and the reason the new one was
+    /// rejected for `reload_report`.
+    pub(crate) fn reject_reload(&mut self, error: String) {
+        log(
+            format!("Config reload rejected:\n{}", error),
+            LogLevel::Error,
+        );
+        self.reload_error = Some((SystemTime::now(), error));
+    }
+
+    /// When the running config was loaded, and why the latest reload was
+    /// rejected, if it was.
+    pub fn reload_report(&self) -> ReloadReport {
+        ReloadReport::new(self.loaded_at, self.reload_error.as_ref())
     }
 
     pub fn get_node_ids(&self) -> Vec<String> {
-        let mut ids: Vec<String> = Vec::with_capacity(self.nodes.len());
-        for node in self.nodes.iter() {
-            ids.push(node.id.clone());
+        let mut ids: Vec<String> = Vec::with_capacity(self.groups.len());
+        for group in self.groups.iter() {
+            ids.push(group.id.clone());
         }
         ids
     }
@@ -83,27 +209,607 @@ impl HealthChecker {
         self.nodes[u].status()
     }
 
+    /// Aggregate status of all the services under `id`, combined with the
+    /// group's policy.
     pub fn status_by_id(&self, id: &str) -> Option<NodeStatus> {
+        self.group(id).map(|group| {
+            let members: Vec<(NodeStatus, u32)> = group
+                .members
+                .iter()
+                .map(|&(u, weight)| (self.nodes[u].status(), weight))
+                .collect();
+            group.evaluate(&members)
+        })
+    }
+
+    /// HTTP status code to answer while `id` is degraded.
+    pub fn degraded_code_by_id(&self, id: &str) -> Option<u16> {
+        self.group(id).map(|group| group.degraded_code)
+    }
+
+    /// Urls of the services under `id` that are currently down.
+    pub fn failed_by_id(&self, id: &str) -> Option<Vec<String>> {
+        self.group(id).map(|group| {
+            group
+                .members
+                .iter()
+                .map(|&(u, _)| &self.nodes[u])
+                .filter(|node| node.status() == NodeStatus::Down)
+                .map(|node| node.url().to_string())
+                .collect()
+        })
+    }
+
+    /// Urls of the services under `id` that are currently degraded.
+    pub fn degraded_by_id(&self, id: &str) -> Option<Vec<String>> {
+        self.group(id).map(|group| {
+            group
+                .members
+                .iter()
+                .map(|&(u, _)| &self.nodes[u])
+                .filter(|node| node.status() == NodeStatus::Degraded)
+                .map(|node| node.url().to_string())
+                .collect()
+        })
+    }
+
+    /// Urls of the heartbeat services under `id`, which are never probed.
+    pub fn heartbeats_by_id(&self, id: &str) -> Option<Vec<String>> {
+        self.group(id).map(|group| {
+            group
+                .members
+                .iter()
+                .map(|&(u, _)| &self.nodes[u])
+                .filter(|node| node.is_heartbeat())
+                .map(|node| node.url().to_string())
+                .collect()
+        })
+    }
+
+    /// Urls of the services under `id` that are flapping.
+    pub fn flapping_by_id(&self, id: &str) -> Option<Vec<String>> {
+        self.group(id).map(|group| {
+            group
+                .members
+                .iter()
+                .map(|&(u, _)| &self.nodes[u])
+                .filter(|node| node.is_flapping())
+                .map(|node| node.url().to_string())
+                .collect()
+        })
+    }
+
+    /// Structured status of every id, in config order.
+    pub fn report(&self) -> Vec<GroupReport> {
+        self.groups
+            .iter()
+            .map(|group| self.report_by_id(&group.id).unwrap())
+            .collect()
+    }
+
+    pub fn report_by_id(&self, id: &str) -> Option<GroupReport> {
+        let group = self.group(id)?;
+
+        Some(GroupReport {
+            id: group.id.clone(),
+            status: self.status_by_id(id)?,
+            services: group
+                .members
+                .iter()
+                .map(|&(u, _)| ServiceReport::new(&self.nodes[u]))
+                .collect(),
+        })
+    }
+
+    fn group(&self, id: &str) -> Option<&NodeGroup> {
+        self.groups.iter().find(|group| group.id == id)
+    }
+
+    /// The pooled HTTP client shared by every probe.
+    pub fn client(&self) -> reqwest::Client {
+        self.client.clone()
+    }
+
+    pub async fn check(&mut self, u: usize) -> Result<NodeStatus, Box<dyn Error + Send + Sync>> {
+        let status = self.nodes[u].check(&self.client).await;
+        self.publish();
+        status
+    }
+
+    /// Checks every due service under `id` concurrently and returns the
+    /// group's aggregate status.
+    pub async fn check_by_id(
+        &mut self,
+        id: &str,
+    ) -> Result<NodeStatus, Box<dyn Error + Send + Sync>> {
+        self.check_ids(&[id.to_string()]).await?;
+        Ok(self.status_by_id(id).unwrap())
+    }
+
+    /// Passes a ping on to the heartbeat services under `id`. Returns false
+    /// if there is no such id, or it has no heartbeat services.
+    pub fn ping(&mut self, id: &str, ping: Ping, message: Option<&str>) -> bool {
+        let members: Vec<usize> = match self.group(id) {
+            Some(group) => group.members.iter().map(|&(u, _)| u).collect(),
+            None => return false,
+        };
+        let mut pinged = false;
+        for u in members {
+            pinged |= self.nodes[u].ping(ping, message);
+        }
+        self.publish();
+        pinged
+    }
+
+    /// Checks every due node concurrently, each on its own task.
+    pub async fn check_all(&mut self) {
+        let probes = self.due_probes();
+        self.run_probes(probes).await;
+    }
+
+    /// Like `check_all`, but only checks the services under `ids`.
+    pub async fn check_ids(&mut self, ids: &[String]) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut members = Vec::new();
+        for id in ids {
+            match self.group(id) {
+                Some(group) => members.extend(group.members.iter().map(|&(u, _)| u)),
+                None => return Err(format!("Cannot find node '{}'", id).into()),
+            }
+        }
+        members.sort_unstable();
+        members.dedup();
+        let probes = members
+            .into_iter()
+            .filter_map(|u| {
+                let node = &mut self.nodes[u];
+                node.start_check().map(|probe| (node.key(), probe))
+            })
+            .collect();
+        self.run_probes(probes).await;
+        Ok(())
+    }
+
+    async fn run_probes(&mut self, probes: Vec<(u64, Probe)>) {
+        let tasks: Vec<_> = probes
+            .into_iter()
+            .map(|(key, probe)| {
+                let client = self.client.clone();
+                (key, tokio::spawn(async move { probe.run(&client).await }))
+            })
+            .collect();
+
+        for (key, task) in tasks {
+            let result = match task.await {
+                Ok(result) => result,
+                Err(err) => CheckResult::failed(err.to_string()),
+            };
+            self.record(key, result);
+        }
+    }
+
+    /// Starts a check on every node whose interval has elapsed and returns
+    /// the probes to run, keyed by node key.
+    pub(crate) fn due_probes(&mut self) -> Vec<(u64, Probe)> {
         self.nodes
+            .iter_mut()
+            .filter_map(|node| node.start_check().map(|probe| (node.key(), probe)))
+            .collect()
+    }
+
+    /// Records the result of the node with `key`. Nodes kept by a reload
+    /// since their probe started still get it; nodes that were dropped or
+    /// replaced don't.
+    pub(crate) fn record(&mut self, key: u64, result: CheckResult) {
+        match self.nodes.iter_mut().find(|node| node.key() == key) {
+            Some(node) => node.record(result),
+            None => {
+                log(
+                    "Dropping a result of a service removed by a reload".to_string(),
+                    LogLevel::Debug,
+                );
+                return;
+            }
+        }
+        self.publish();
+    }
+
+    /// Follows the aggregate status of every id, in config order. A new
+    /// value is sent whenever one of them changes.
+    pub fn subscribe(&self) -> watch::Receiver<Vec<(String, NodeStatus)>> {
+        self.statuses.subscribe()
+    }
+
+    fn publish(&self) {
+        let statuses: Vec<(String, NodeStatus)> = self
+            .groups
             .iter()
-            .find(|&x| x.id == id)
-            .map(|node| node.status())
+            .map(|group| (group.id.clone(), self.status_by_id(&group.id).unwrap()))
+            .collect();
+        self.statuses.send_if_modified(|current| {
+            if *current == statuses {
+                return false;
+            }
+            *current = statuses;
+            true
+        });
     }
 
-    pub fn check(&mut self, u: usize) -> Result<NodeStatus, Box<dyn Error>> {
-        self.nodes[u].check()
+    /// Every node, in config order.
+    pub(crate) fn nodes(&self) -> &[Node] {
+        &self.nodes
     }
+}
 
-    pub fn check_by_id(&mut self, id: &str) -> Result<NodeStatus, Box<dyn Error>> {
-        match self.nodes.iter_mut().find(|x| x.id == id) {
-            Some(x) => x.check(),
-            None => Err("Cannot find node".into()),
+/// Builds the node of one service, or records what's wrong with it.
+fn build_node(
+    id: &str,
+    service: &ServiceConfig,
+    path: &str,
+    errors: &mut ConfigError,
+) -> Option<Node> {
+    let counts = [
+        ("interval", service.interval),
+        ("call_timeout", service.call_timeout),
+        ("rise", service.rise as u64),
+        ("fall", service.fall as u64),
+    ];
+    for (field, count) in counts {
+        if count == 0 {
+            errors.push(&format!("{}.{}", path, field), "must be greater than 0");
         }
     }
+    let node_config = build_node_config(id, service, path, errors);
+    let strategy = build_strategy(&service.strategy(), path, errors);
+    let (node_config, strategy) = (node_config?, strategy?);
+
+    let method = match service.method {
+        Method::Post => RequestMethod::POST,
+        Method::Get => RequestMethod::GET,
+    };
+    let request_body = service
+        .request_body
+        .clone()
+        .filter(|request_body| !request_body.is_empty());
+    let thresholds = Thresholds {
+        rise: service.rise,
+        fall: service.fall,
+        flap_window: Duration::from_secs(service.flap_window),
+        flap_threshold: service.flap_threshold,
+    };
+    let retry = RetryPolicy {
+        retries: service.retries,
+        backoff: Duration::from_millis(service.retry_backoff_ms),
+        deadline: service.retry_deadline.map(Duration::from_secs),
+    };
+
+    Some(
+        Node::new(
+            node_config,
+            id.to_string(),
+            strategy,
+            service.interval,
+            method,
+            request_body,
+            service.call_timeout,
+        )
+        .with_thresholds(thresholds)
+        .with_retry(retry),
+    )
+}
+
+/// Reads what kind of service it is and where it lives.
+fn build_node_config(
+    id: &str,
+    service: &ServiceConfig,
+    path: &str,
+    errors: &mut ConfigError,
+) -> Option<NodeConfig> {
+    let field = |name: &str| format!("{}.{}", path, name);
+    let condition = format!("type={}", service.service_type.as_str());
+    let address = |errors: &mut ConfigError| {
+        required(&service.address, &field("address"), &condition, errors)
+            .map(|address| address.trim().to_string())
+    };
+
+    match service.service_type {
+        ServiceType::Http => {
+            let url = required(&service.url, &field("url"), &condition, errors)?.trim();
+            if let Err(err) = reqwest::Url::parse(url) {
+                errors.push(&field("url"), format!("invalid url: {}", err));
+                return None;
+            }
+            Some(NodeConfig::new(url.to_string()))
+        }
+        ServiceType::Tcp => Some(NodeConfig::tcp(
+            address(errors)?,
+            service.send.clone(),
+            service.expect.clone(),
+        )),
+        ServiceType::Tls => {
+            let address = address(errors)?;
+            // default to the host part of the address
+            let server_name = match &service.server_name {
+                Some(server_name) => server_name.clone(),
+                None => match address.rsplit_once(':') {
+                    Some((host, _)) => host.to_string(),
+                    None => address.clone(),
+                },
+            };
+            let policy = CertPolicy {
+                server_name,
+                ca_file: service.ca_file.clone(),
+                min_days: service.min_days,
+                warn_days: service.warn_days,
+            };
 
-    pub fn check_all(&mut self) {
-        for node in self.nodes.iter_mut() {
-            _ = node.check();
+            Some(NodeConfig::tls(address, policy))
+        }
+        ServiceType::Dns => {
+            let name = required(&service.name, &field("name"), &condition, errors);
+            let record_type = RecordType::parse(service.record_type.as_deref().unwrap_or("A"))
+                .map_err(|err| errors.push(&field("record_type"), err))
+                .ok();
+            let resolver = match &service.resolver {
+                Some(resolver) => parse_resolver(resolver)
+                    .map_err(|err| errors.push(&field("resolver"), err))
+                    .ok(),
+                None => dns::system_resolver().or_else(|| {
+                    errors.push(
+                        &field("resolver"),
+                        "required, none found in /etc/resolv.conf",
+                    );
+                    None
+                }),
+            };
+
+            Some(NodeConfig::dns(DnsQuery {
+                name: name?.trim().to_string(),
+                record_type: record_type?,
+                resolver: resolver?,
+                expected: service
+                    .expected
+                    .iter()
+                    .map(|record| record.trim_end_matches('.').to_string())
+                    .collect(),
+                max_latency: service.max_latency_ms.map(Duration::from_millis),
+            }))
+        }
+        ServiceType::Grpc => Some(NodeConfig::grpc(
+            address(errors)?,
+            GrpcTarget {
+                service: service.service.clone().unwrap_or_default(),
+                tls: service.tls,
+                server_name: service.server_name.clone(),
+                ca_file: service.ca_file.clone(),
+            },
+        )),
+        ServiceType::Postgres => {
+            let login = parse_login(service, "postgres", path, errors);
+            Some(NodeConfig::postgres(address(errors)?, login))
+        }
+        ServiceType::Mysql => {
+            let login = parse_login(service, "root", path, errors);
+            Some(NodeConfig::mysql(address(errors)?, login))
+        }
+        ServiceType::Redis => {
+            let role = service.role.as_deref().and_then(|role| {
+                RedisRole::parse(role)
+                    .map_err(|err| errors.push(&field("role"), err))
+                    .ok()
+            });
+            let password = parse_password(service, path, errors);
+
+            Some(NodeConfig::redis(
+                address(errors)?,
+                RedisCheck {
+                    username: service.user.clone(),
+                    password,
+                    role,
+                    max_lag: service.max_lag_seconds,
+                },
+            ))
+        }
+        ServiceType::Exec => Some(NodeConfig::exec(ExecCommand {
+            program: required(&service.command, &field("command"), &condition, errors)?.to_string(),
+            args: service.args.iter().map(|arg| arg.to_string()).collect(),
+            env: service
+                .env
+                .iter()
+                .map(|(key, value)| (key.clone(), value.to_string()))
+                .collect(),
+        })),
+        ServiceType::Heartbeat => Some(NodeConfig::heartbeat(
+            id,
+            Duration::from_secs(service.grace),
+        )),
+    }
+}
+
+/// Reads the strategy of a service, or of one of its `conditions`. A
+/// `conditions` list combines the strategies of its entries, all of which
+/// have to pass unless `match` is set to `any`.
+fn build_strategy(
+    config: &StrategyConfig,
+    path: &str,
+    errors: &mut ConfigError,
+) -> Option<NodeCheckStrategy> {
+    let field = |name: &str| format!("{}.{}", path, name);
+    if let Some(conditions) = &config.conditions {
+        let strategies: Vec<Option<NodeCheckStrategy>> = conditions
+            .iter()
+            .enumerate()
+            .map(|(k, condition)| {
+                build_strategy(condition, &format!("{}.conditions[{}]", path, k), errors)
+            })
+            .collect();
+        let strategies: Vec<NodeCheckStrategy> = strategies.into_iter().collect::<Option<_>>()?;
+        return Some(match config.match_mode {
+            MatchMode::Any => NodeCheckStrategy::Any(strategies),
+            MatchMode::All => NodeCheckStrategy::All(strategies),
+        });
+    }
+
+    let condition = format!("strategy={}", config.strategy.as_str());
+    match config.strategy {
+        StrategyKind::StringContains => {
+            let contains_string = required(
+                &config.strategy_string,
+                &field("strategy_string"),
+                &condition,
+                errors,
+            )?;
+
+            Some(NodeCheckStrategy::BodyContains(contains_string.to_string()))
+        }
+        StrategyKind::Regex => {
+            let pattern = required(
+                &config.strategy_string,
+                &field("strategy_string"),
+                &condition,
+                errors,
+            )?;
+            match Regex::new(pattern) {
+                Ok(regex) => Some(NodeCheckStrategy::BodyMatches {
+                    regex,
+                    negate: config.negate,
+                }),
+                Err(err) => {
+                    errors.push(&field("strategy_string"), format!("invalid regex: {}", err));
+                    None
+                }
+            }
+        }
+        StrategyKind::Json => {
+            let Some(assertions) = &config.assertions else {
+                errors.push(&field("assertions"), format!("required when {}", condition));
+                return None;
+            };
+            let assertions: Vec<Option<JsonAssertion>> = assertions
+                .iter()
+                .enumerate()
+                .map(|(k, assertion)| {
+                    JsonAssertion::parse(assertion)
+                        .map_err(|err| errors.push(&format!("{}.assertions[{}]", path, k), err))
+                        .ok()
+                })
+                .collect();
+
+            Some(NodeCheckStrategy::JsonAssertions(
+                assertions.into_iter().collect::<Option<_>>()?,
+            ))
+        }
+        StrategyKind::Latency => {
+            let Some(max) = config.max_latency_ms else {
+                errors.push(
+                    &field("max_latency_ms"),
+                    format!("required when {}", condition),
+                );
+                return None;
+            };
+
+            Some(NodeCheckStrategy::MaxLatency {
+                max: Duration::from_millis(max),
+                degrade: config.degraded,
+            })
+        }
+        StrategyKind::StatusCode => match &config.expected_status {
+            None => Some(NodeCheckStrategy::StatusCode), // default strategy
+            Some(expected) => parse_status_ranges(expected)
+                .map(NodeCheckStrategy::ExpectedStatus)
+                .map_err(|err| errors.push(&field("expected_status"), err))
+                .ok(),
+        },
+    }
+}
+
+/// Returns the value of a field that `condition` makes mandatory, or records
+/// that it is missing.
+fn required<'a>(
+    value: &'a Option<String>,
+    path: &str,
+    condition: &str,
+    errors: &mut ConfigError,
+) -> Option<&'a str> {
+    match value.as_deref() {
+        Some(value) if !value.trim().is_empty() => Some(value),
+        _ => {
+            errors.push(path, format!("required when {}", condition));
+            None
+        }
+    }
+}
+
+/// Reads the password of a service, given directly or through the
+/// environment variable named by `password_env`.
+fn parse_password(service: &ServiceConfig, path: &str, errors: &mut ConfigError) -> Option<String> {
+    match &service.password_env {
+        Some(var) => env::var(var)
+            .map_err(|_| {
+                errors.push(
+                    &format!("{}.password_env", path),
+                    format!("environment variable {} not set", var),
+                )
+            })
+            .ok(),
+        None => service.password.clone(),
+    }
+}
+
+/// Reads the credentials and query of a database service.
+fn parse_login(
+    service: &ServiceConfig,
+    default_user: &str,
+    path: &str,
+    errors: &mut ConfigError,
+) -> DatabaseLogin {
+    DatabaseLogin {
+        user: service
+            .user
+            .clone()
+            .unwrap_or_else(|| default_user.to_string()),
+        password: parse_password(service, path, errors),
+        database: service.database.clone(),
+        query: service.query.clone(),
+        expect: service.expect.clone(),
+        tls: service.tls,
+        ca_file: service.ca_file.clone(),
+    }
+}
+
+/// Parses a resolver address, with the port defaulting to 53.
+fn parse_resolver(resolver: &str) -> Result<SocketAddr, String> {
+    let resolver = resolver.trim();
+    resolver
+        .parse()
+        .or_else(|_| resolver.parse::<IpAddr>().map(|ip| SocketAddr::new(ip, 53)))
+        .map_err(|_| format!("invalid DNS resolver: {}", resolver))
+}
+
+/// Reads `expected_status`: a code (`200`), a range (`"200-299"`) or a list
+/// of either.
+fn parse_status_ranges(expected: &ExpectedStatus) -> Result<Vec<RangeInclusive<u16>>, String> {
+    match expected {
+        ExpectedStatus::Code(code) => Ok(vec![*code..=*code]),
+        ExpectedStatus::Range(range) => {
+            let parse_code = |code: &str| {
+                code.trim()
+                    .parse::<u16>()
+                    .map_err(|_| format!("invalid status code: {}", range))
+            };
+            match range.split_once('-') {
+                Some((from, to)) => Ok(vec![parse_code(from)?..=parse_code(to)?]),
+                None => {
+                    let code = parse_code(range)?;
+                    Ok(vec![code..=code])
+                }
+            }
+        }
+        ExpectedStatus::List(entries) => {
+            let mut ranges = Vec::new();
+            for entry in entries {
+                ranges.extend(parse_status_ranges(entry)?);
+            }
+            Ok(ranges)
         }
     }
 }
@@ -111,9 +817,10 @@ impl HealthChecker {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::healthcheck::testing::serve;
 
-    #[test]
-    fn test_check_success() {
+    #[tokio::test]
+    async fn test_check_success() {
         let data = r#"
         [
         {
@@ -126,18 +833,18 @@ mod tests {
         }
         ]"#;
 
-        let mut checker = HealthChecker::new(data.to_string());
+        let mut checker = HealthChecker::new(data.to_string()).unwrap();
         assert_eq!(checker.nodes.len(), 1);
         assert_eq!(checker.nodes[0].id, "test");
         assert_eq!(checker.status(0), NodeStatus::Processing);
 
-        checker.check_all();
+        checker.check_all().await;
 
         assert_eq!(checker.status(0), NodeStatus::Down);
     }
 
-    #[test]
-    fn test_check_multiple() {
+    #[tokio::test]
+    async fn test_check_multiple() {
         let data = r#"
         [
         {
@@ -169,9 +876,9 @@ mod tests {
         }
         ]"#;
 
-        let mut checker = HealthChecker::new(data.to_string());
+        let mut checker = HealthChecker::new(data.to_string()).unwrap();
 
-        checker.check_all();
+        checker.check_all().await;
 
         assert_eq!(checker.status(0), NodeStatus::Down);
         assert_eq!(checker.status(1), NodeStatus::Healthy);
@@ -182,8 +889,8 @@ mod tests {
         assert_eq!(checker.status_by_id("test3").unwrap(), NodeStatus::Down);
     }
 
-    #[test]
-    fn test_stringcontains_strategy() {
+    #[tokio::test]
+    async fn test_stringcontains_strategy() {
         let data = r#"
         [
         {
@@ -199,15 +906,15 @@ mod tests {
         }
         ]"#;
 
-        let mut checker = HealthChecker::new(data.to_string());
-        _ = checker.check_by_id("test1");
+        let mut checker = HealthChecker::new(data.to_string()).unwrap();
+        _ = checker.check_by_id("test1").await;
 
         assert_eq!(checker.status(0), NodeStatus::Healthy);
 
         assert_eq!(checker.status_by_id("test1").unwrap(), NodeStatus::Healthy);
     }
-    #[test]
-    fn test_stringcontains_strategy_fails() {
+    #[tokio::test]
+    async fn test_stringcontains_strategy_fails() {
         let data = r#"
         [
         {
@@ -223,16 +930,16 @@ mod tests {
         }
         ]"#;
 
-        let mut checker = HealthChecker::new(data.to_string());
+        let mut checker = HealthChecker::new(data.to_string()).unwrap();
 
-        _ = checker.check_by_id("test1");
+        _ = checker.check_by_id("test1").await;
 
         assert_eq!(checker.status(0), NodeStatus::Down);
         assert_eq!(checker.status_by_id("test1").unwrap(), NodeStatus::Down);
     }
 
-    #[test]
-    fn test_post_method() {
+    #[tokio::test]
+    async fn test_post_method() {
         let data = r#"
         [
         {
@@ -249,10 +956,416 @@ mod tests {
         }
         ]"#;
 
-        let mut checker = HealthChecker::new(data.to_string());
+        let mut checker = HealthChecker::new(data.to_string()).unwrap();
+
+        _ = checker.check_by_id("test1").await;
+
+        assert_eq!(checker.status(0), NodeStatus::Healthy);
+    }
+
+    #[tokio::test]
+    async fn test_group_policies() {
+        let up = serve("HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 2\r\n\r\nok");
+        let down = "http://127.0.0.1:1/";
+        let data = format!(
+            r#"[
+            {{"id":"all","services":[{{"url":"{up}"}},{{"url":"{down}"}}]}},
+            {{"id":"any","policy":"any","services":[{{"url":"{up}"}},{{"url":"{down}"}}]}},
+            {{"id":"quorum","policy":{{"quorum":2}},"services":[{{"url":"{up}"}},{{"url":"{down}"}},{{"url":"{up}"}}]}},
+            {{"id":"weighted","policy":{{"weighted":0.5}},"services":[{{"url":"{up}"}},{{"url":"{down}","weight":3}}]}}
+            ]"#
+        );
+
+        let mut checker = HealthChecker::new(data).unwrap();
+        assert_eq!(checker.get_node_ids().len(), 4);
+        checker.check_all().await;
+
+        assert_eq!(checker.status_by_id("all").unwrap(), NodeStatus::Down);
+        assert_eq!(checker.status_by_id("any").unwrap(), NodeStatus::Healthy);
+        assert_eq!(checker.status_by_id("quorum").unwrap(), NodeStatus::Healthy);
+        assert_eq!(checker.status_by_id("weighted").unwrap(), NodeStatus::Down);
+        assert_eq!(checker.failed_by_id("any").unwrap(), vec![down.to_string()]);
+        assert!(checker.status_by_id("missing").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_report() {
+        let broken = serve(
+            "HTTP/1.1 503 Service Unavailable\r\nConnection: close\r\nContent-Length: 0\r\n\r\n",
+        );
+        let data = format!(r#"[{{"id":"test","services":[{{"url":"{}"}}]}}]"#, broken);
+
+        let mut checker = HealthChecker::new(data).unwrap();
+        let report = checker.report_by_id("test").unwrap();
+        assert_eq!(report.status, NodeStatus::Processing);
+        assert!(report.services[0].last_check.is_none());
+
+        checker.check_all().await;
+
+        let report = checker.report_by_id("test").unwrap();
+        assert_eq!(report.id, "test");
+        assert_eq!(report.status, NodeStatus::Down);
+        let service = &report.services[0];
+        assert_eq!(service.url, broken);
+        assert_eq!(service.http_code, Some(503));
+        assert_eq!(service.consecutive_failures, 1);
+        assert!(service.last_check.is_some());
+        assert!(service.latency_ms.is_some());
+        assert!(service.error.as_ref().unwrap().contains("503"));
+        assert_eq!(checker.report().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_regex_strategy() {
+        let degraded = serve(
+            "HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 14\r\n\r\nstate=degraded",
+        );
+        let data = format!(
+            r#"[
+            {{"id":"match","services":[{{"url":"{url}","strategy":"regex","strategy_string":"state=\\w+"}}]}},
+            {{"id":"nomatch","services":[{{"url":"{url}","strategy":"regex","strategy_string":"^ok$"}}]}},
+            {{"id":"negate","services":[{{"url":"{url}","strategy":"regex","strategy_string":"ERROR|degraded","negate":true}}]}}
+            ]"#,
+            url = degraded
+        );
+
+        let mut checker = HealthChecker::new(data).unwrap();
+        checker.check_all().await;
+
+        assert_eq!(checker.status_by_id("match").unwrap(), NodeStatus::Healthy);
+        assert_eq!(checker.status_by_id("nomatch").unwrap(), NodeStatus::Down);
+        assert_eq!(checker.status_by_id("negate").unwrap(), NodeStatus::Down);
+    }
+
+    #[test]
+    fn test_invalid_regex() {
+        let data = r#"[{"id":"test","services":[{"url":"http://localhost","strategy":"regex","strategy_string":"(unclosed"}]}]"#;
+        let err = HealthChecker::new(data.to_string()).err().unwrap();
+        assert!(err.errors()[0].starts_with("[0].services[0].strategy_string: invalid regex"));
+    }
+
+    #[tokio::test]
+    async fn test_json_strategy() {
+        let health = serve("HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 38\r\n\r\n{\"status\":\"UP\",\"db\":{\"status\":\"DOWN\"}}");
+        let data = format!(
+            r#"[
+            {{"id":"up","services":[{{"url":"{url}","strategy":"json","assertions":[{{"path":"$.status","equals":"UP"}}]}}]}},
+            {{"id":"db","services":[{{"url":"{url}","strategy":"json","assertions":[
+                {{"path":"$.status","equals":"UP"}},
+                {{"pointer":"/db/status","in":["UP","OK"]}}
+            ]}}]}}
+            ]"#,
+            url = health
+        );
+
+        let mut checker = HealthChecker::new(data).unwrap();
+        checker.check_all().await;
+
+        assert_eq!(checker.status_by_id("up").unwrap(), NodeStatus::Healthy);
+        assert_eq!(checker.status_by_id("db").unwrap(), NodeStatus::Down);
+        let report = checker.report_by_id("db").unwrap();
+        assert_eq!(
+            report.services[0].error.as_deref(),
+            Some(r#"Assertion failed: /db/status in ["UP","OK"] (got "DOWN")"#)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_conditions() {
+        let created =
+            serve("HTTP/1.1 201 Created\r\nConnection: close\r\nContent-Length: 2\r\n\r\nok");
+        let data = format!(
+            r#"[
+            {{"id":"all","services":[{{"url":"{url}","conditions":[
+                {{"expected_status":[200,"201-204"]}},
+                {{"strategy":"stringcontains","strategy_string":"ok"}},
+                {{"strategy":"latency","max_latency_ms":5000}}
+            ]}}]}},
+            {{"id":"code","services":[{{"url":"{url}","conditions":[
+                {{"expected_status":200}},
+                {{"strategy":"stringcontains","strategy_string":"ok"}}
+            ]}}]}},
+            {{"id":"any","services":[{{"url":"{url}","match":"any","conditions":[
+                {{"expected_status":200}},
+                {{"strategy":"stringcontains","strategy_string":"ok"}}
+            ]}}]}},
+            {{"id":"slow","services":[{{"url":"{url}","conditions":[
+                {{"strategy":"latency","max_latency_ms":0,"degraded":true}}
+            ]}}]}}
+            ]"#,
+            url = created
+        );
+
+        let mut checker = HealthChecker::new(data).unwrap();
+        checker.check_all().await;
+
+        assert_eq!(checker.status_by_id("all").unwrap(), NodeStatus::Healthy);
+        assert_eq!(checker.status_by_id("code").unwrap(), NodeStatus::Down);
+        assert_eq!(checker.status_by_id("any").unwrap(), NodeStatus::Healthy);
+        assert_eq!(checker.status_by_id("slow").unwrap(), NodeStatus::Degraded);
+    }
+
+    #[test]
+    fn test_reload_keeps_results_of_kept_nodes() {
+        let data = r#"[
+            {"id":"kept","services":[{"url":"http://127.0.0.1:1/"}]},
+            {"id":"changed","services":[{"url":"http://127.0.0.1:2/"}]}
+        ]"#;
+        let mut checker = HealthChecker::new(data.to_string()).unwrap();
+        let keys: Vec<u64> = checker
+            .due_probes()
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+        assert_eq!(keys.len(), 2);
+
+        let data = r#"[
+            {"id":"kept","services":[{"url":"http://127.0.0.1:1/"}]},
+            {"id":"changed","services":[{"url":"http://127.0.0.1:2/","interval":30}]}
+        ]"#;
+        checker.reload(data, ConfigFormat::Json).unwrap();
+        for key in keys {
+            checker.record(key, CheckResult::failed("refused".to_string()));
+        }
+        assert_eq!(checker.status_by_id("kept"), Some(NodeStatus::Down));
+        assert_eq!(
+            checker.status_by_id("changed"),
+            Some(NodeStatus::Processing)
+        );
+    }
+
+    #[test]
+    fn test_config_errors() {
+        let data = r#"[
+            {"id":"web","degraded_code":100,"services":[{"url":"http://localhost"}]},
+            {"id":"api","services":[
+                {"url":"http://localhost","strategy":"stringcontains"},
+                {"type":"tcp"},
+                {"url":"http://localhost","conditions":[{"strategy":"latency"}]}
+            ]}
+        ]"#;
+
+        let err = HealthChecker::new(data.to_string()).err().unwrap();
+        assert_eq!(
+            err.errors(),
+            [
+                "[0].degraded_code: must be between 200 and 599",
+                "[1].services[0].strategy_string: required when strategy=stringcontains",
+                "[1].services[1].address: required when type=tcp",
+                "[1].services[2].conditions[0].max_latency_ms: required when strategy=latency",
+            ]
+        );
+    }
+
+    fn expected_status(value: serde_json::Value) -> ExpectedStatus {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_parse_status_ranges() {
+        let ranges =
+            parse_status_ranges(&expected_status(serde_json::json!([200, "300-399", "418"])));
+        assert_eq!(ranges.unwrap(), vec![200..=200, 300..=399, 418..=418]);
+    }
+
+    #[test]
+    fn test_parse_status_ranges_invalid() {
+        let err = parse_status_ranges(&expected_status(serde_json::json!(["2xx"])));
+        assert_eq!(err.unwrap_err(), "invalid status code: 2xx");
+    }
 
-        _ = checker.check_by_id("test1");
+    #[tokio::test]
+    async fn test_tcp_service() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let data = format!(
+            r#"[
+            {{"id":"db","policy":"any","services":[
+                {{"type":"tcp","address":"{}"}},
+                {{"type":"tcp","address":"127.0.0.1:1"}}
+            ]}}
+            ]"#,
+            address
+        );
+
+        let mut checker = HealthChecker::new(data).unwrap();
+        checker.check_all().await;
 
         assert_eq!(checker.status(0), NodeStatus::Healthy);
+        assert_eq!(checker.status(1), NodeStatus::Down);
+        assert_eq!(checker.status_by_id("db").unwrap(), NodeStatus::Healthy);
+        assert_eq!(checker.failed_by_id("db").unwrap(), vec!["127.0.0.1:1"]);
+    }
+
+    #[tokio::test]
+    async fn test_dns_service() {
+        // a port nobody listens on, so the query is refused right away
+        let resolver = std::net::UdpSocket::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let data = format!(
+            r#"[
+            {{"id":"dns","services":[
+                {{"type":"dns","name":"db.internal","record_type":"srv","resolver":"{}",
+                 "expected":["10 5 5432 db.internal."],"max_latency_ms":50}}
+            ]}}
+            ]"#,
+            resolver
+        );
+
+        let mut checker = HealthChecker::new(data).unwrap();
+        checker.check_all().await;
+
+        assert_eq!(checker.status_by_id("dns").unwrap(), NodeStatus::Down);
+        assert_eq!(checker.failed_by_id("dns").unwrap(), vec!["db.internal"]);
+        assert_eq!(
+            parse_resolver("10.0.0.53").unwrap(),
+            "10.0.0.53:53".parse().unwrap()
+        );
+        assert_eq!(
+            parse_resolver("[::1]:5353").unwrap(),
+            "[::1]:5353".parse().unwrap()
+        );
+        assert!(parse_resolver("dns.internal").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_service() {
+        let data = r#"[
+            {"id":"backup","services":[{"type":"heartbeat","interval":3600,"grace":0}]},
+            {"id":"web","services":[{"url":"http://127.0.0.1:1/"}]}
+            ]"#;
+
+        let mut checker = HealthChecker::new(data.to_string()).unwrap();
+        checker.check_all().await;
+        assert_eq!(
+            checker.status_by_id("backup").unwrap(),
+            NodeStatus::Processing
+        );
+
+        assert!(checker.ping("backup", Ping::Start, None));
+        assert!(checker.ping("backup", Ping::Fail, Some("disk full")));
+        assert_eq!(checker.status_by_id("backup").unwrap(), NodeStatus::Down);
+        let report = checker.report_by_id("backup").unwrap();
+        assert_eq!(report.services[0].url, "/heartbeat/backup");
+        assert_eq!(report.services[0].output.as_deref(), Some("disk full"));
+
+        assert!(checker.ping("backup", Ping::Success, None));
+        assert_eq!(checker.status_by_id("backup").unwrap(), NodeStatus::Healthy);
+
+        assert!(!checker.ping("web", Ping::Success, None));
+        assert!(!checker.ping("missing", Ping::Success, None));
+    }
+
+    #[tokio::test]
+    async fn test_check_all_local() {
+        let up = serve("HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 2\r\n\r\nok");
+        let broken = serve(
+            "HTTP/1.1 503 Service Unavailable\r\nConnection: close\r\nContent-Length: 0\r\n\r\n",
+        );
+        let data = format!(
+            r#"[
+            {{"id":"up","services":[{{"url":"{}"}}]}},
+            {{"id":"broken","services":[{{"url":"{}"}}]}},
+            {{"id":"refused","services":[{{"url":"http://127.0.0.1:1/"}}]}}
+            ]"#,
+            up, broken
+        );
+
+        let mut checker = HealthChecker::new(data).unwrap();
+        checker.check_all().await;
+
+        assert_eq!(checker.status_by_id("up").unwrap(), NodeStatus::Healthy);
+        assert_eq!(checker.status_by_id("broken").unwrap(), NodeStatus::Down);
+        assert_eq!(checker.status_by_id("refused").unwrap(), NodeStatus::Down);
+    }
+
+    #[tokio::test]
+    async fn test_check_ids() {
+        let up = serve("HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 0\r\n\r\n");
+        let data = format!(
+            r#"[
+            {{"id":"up","services":[{{"url":"{}"}}]}},
+            {{"id":"refused","services":[{{"url":"http://127.0.0.1:1/"}}]}}
+            ]"#,
+            up
+        );
+
+        let mut checker = HealthChecker::new(data).unwrap();
+        checker.check_ids(&["up".to_string()]).await.unwrap();
+        assert_eq!(checker.status_by_id("up").unwrap(), NodeStatus::Healthy);
+        assert_eq!(
+            checker.status_by_id("refused").unwrap(),
+            NodeStatus::Processing
+        );
+        assert!(checker.check_ids(&["missing".to_string()]).await.is_err());
+    }
+
+    #[test]
+    fn test_metrics_path_reserved() {
+        let data = r#"[{"id":"stats","services":[{"type":"heartbeat","interval":60}]}]"#;
+        let err = HealthChecker::load(data, ConfigFormat::Json, "/stats")
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.errors(),
+            ["[0].id: collides with the metrics path /stats"]
+        );
+
+        let web = r#"[{"id":"web","services":[{"type":"heartbeat","interval":60}]}]"#;
+        let mut checker = HealthChecker::load(web, ConfigFormat::Json, "/stats").unwrap();
+        assert!(checker.reload(data, ConfigFormat::Json).is_err());
+        assert_eq!(checker.get_node_ids(), ["web"]);
+        assert_eq!(
+            checker.reload_report().error.unwrap(),
+            "[0].id: collides with the metrics path /stats"
+        );
+    }
+
+    #[test]
+    fn test_config_validation() {
+        let data = r#"[
+            {"id":"web","services":[{"url":"http://localhost","interval":0,"rise":0}]},
+            {"id":"api","policy":{"quorum":3},"services":[
+                {"url":"http://localhost:1","call_timeout":0,"fall":0},
+                {"url":"http://localhost:2"}
+            ]},
+            {"id":"web","services":[{"url":"http://localhost:3"}]}
+        ]"#;
+
+        let err = HealthChecker::new(data.to_string()).err().unwrap();
+        assert_eq!(
+            err.errors(),
+            [
+                "[0].services[0].interval: must be greater than 0",
+                "[0].services[0].rise: must be greater than 0",
+                "[1].policy: quorum of 3 is more than the 2 services",
+                "[1].services[0].call_timeout: must be greater than 0",
+                "[1].services[0].fall: must be greater than 0",
+                "[2].id: duplicate of [0].id",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_by_id_concurrently() {
+        // accepts connections but never answers
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let data = format!(
+            r#"[{{"id":"slow","services":[
+                {{"url":"{url}","call_timeout":1}},
+                {{"url":"{url}","call_timeout":1}},
+                {{"url":"{url}","call_timeout":1}}
+            ]}}]"#
+        );
+
+        let mut checker = HealthChecker::new(data).unwrap();
+        let started = std::time::Instant::now();
+        let status = checker.check_by_id("slow").await.unwrap();
+        assert_eq!(status, NodeStatus::Down);
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert!(checker.check_by_id("missing").await.is_err());
     }
 }
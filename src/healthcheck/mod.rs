@@ -1,3 +1,9 @@
+pub mod group;
 pub mod health_checker;
 pub mod node;
 pub mod parser;
+pub mod probe;
+pub mod report;
+pub mod scheduler;
+#[cfg(test)]
+pub(crate) mod testing;
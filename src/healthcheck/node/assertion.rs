@@ -0,0 +1,206 @@
+use serde_json::Value;
+
+/// How the value found at an assertion's path is compared.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Comparator {
+    Equals(Value),
+    In(Vec<Value>),
+    GreaterThan(f64),
+    LessThan(f64),
+    /// Whether the path has to be present (`true`) or absent (`false`).
+    Exists(bool),
+}
+
+/// A condition on one value of a JSON response body, e.g.
+/// `{"path": "$.db.status", "equals": "UP"}`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonAssertion {
+    /// The path as written in the config, used in error messages.
+    pub path: String,
+    pointer: String,
+    comparator: Comparator,
+}
+
+impl JsonAssertion {
+    /// Reads an assertion object. The value is addressed either by a JSON
+    /// pointer (`"pointer": "/db/status"`) or a simple JSONPath
+    /// (`"path": "$.db.status"`), and compared with exactly one of `equals`,
+    /// `in`, `gt`, `lt` or `exists`.
+    pub fn parse(assertion: &Value) -> Result<Self, String> {
+        let (path, pointer) = match (assertion["pointer"].as_str(), assertion["path"].as_str()) {
+            (Some(pointer), None) => (pointer.to_string(), pointer.to_string()),
+            (None, Some(path)) => (path.to_string(), path_to_pointer(path)?),
+            _ => return Err("Assertion needs exactly one of 'pointer' or 'path'".to_string()),
+        };
+
+        let mut comparators = Vec::new();
+        if let Some(expected) = assertion.get("equals") {
+            comparators.push(Comparator::Equals(expected.clone()));
+        }
+        if let Some(expected) = assertion.get("in") {
+            match expected.as_array() {
+                Some(set) => comparators.push(Comparator::In(set.clone())),
+                None => return Err(format!("Assertion '{}': 'in' must be an array", path)),
+            }
+        }
+        if let Some(bound) = assertion.get("gt") {
+            match bound.as_f64() {
+                Some(bound) => comparators.push(Comparator::GreaterThan(bound)),
+                None => return Err(format!("Assertion '{}': 'gt' must be a number", path)),
+            }
+        }
+        if let Some(bound) = assertion.get("lt") {
+            match bound.as_f64() {
+                Some(bound) => comparators.push(Comparator::LessThan(bound)),
+                None => return Err(format!("Assertion '{}': 'lt' must be a number", path)),
+            }
+        }
+        if let Some(exists) = assertion.get("exists") {
+            match exists.as_bool() {
+                Some(exists) => comparators.push(Comparator::Exists(exists)),
+                None => return Err(format!("Assertion '{}': 'exists' must be a boolean", path)),
+            }
+        }
+
+        if comparators.len() != 1 {
+            return Err(format!(
+                "Assertion '{}' needs exactly one of 'equals', 'in', 'gt', 'lt' or 'exists'",
+                path
+            ));
+        }
+
+        Ok(Self {
+            path,
+            pointer,
+            comparator: comparators.remove(0),
+        })
+    }
+
+    /// Checks the assertion against a parsed body, describing the failure if
+    /// it doesn't hold.
+    pub fn evaluate(&self, document: &Value) -> Result<(), String> {
+        let found = document.pointer(&self.pointer);
+        let holds = match (&self.comparator, found) {
+            (Comparator::Exists(exists), found) => found.is_some() == *exists,
+            (_, None) => return Err(format!("{} not found", self.path)),
+            (Comparator::Equals(expected), Some(found)) => found == expected,
+            (Comparator::In(set), Some(found)) => set.contains(found),
+            (Comparator::GreaterThan(bound), Some(found)) => {
+                found.as_f64().map(|n| n > *bound).unwrap_or(false)
+            }
+            (Comparator::LessThan(bound), Some(found)) => {
+                found.as_f64().map(|n| n < *bound).unwrap_or(false)
+            }
+        };
+        if holds {
+            return Ok(());
+        }
+
+        let expected = match &self.comparator {
+            Comparator::Equals(expected) => format!("equals {}", expected),
+            Comparator::In(set) => format!("in {}", Value::Array(set.clone())),
+            Comparator::GreaterThan(bound) => format!("> {}", bound),
+            Comparator::LessThan(bound) => format!("< {}", bound),
+            Comparator::Exists(true) => "exists".to_string(),
+            Comparator::Exists(false) => "does not exist".to_string(),
+        };
+        let got = found
+            .map(|found| found.to_string())
+            .unwrap_or_else(|| "nothing".to_string());
+
+        Err(format!(
+            "Assertion failed: {} {} (got {})",
+            self.path, expected, got
+        ))
+    }
+}
+
+/// Converts a JSONPath made of member and index accessors, like
+/// `$.checks[0].status` or `$['db']['status']`, into a JSON pointer.
+fn path_to_pointer(path: &str) -> Result<String, String> {
+    let invalid = || format!("Unsupported JSONPath: {}", path);
+    let mut rest = path.strip_prefix('$').unwrap_or(path);
+    let mut pointer = String::new();
+
+    while !rest.is_empty() {
+        let token;
+        if let Some(member) = rest.strip_prefix('.') {
+            let end = member.find(['.', '[']).unwrap_or(member.len());
+            token = &member[..end];
+            rest = &member[end..];
+        } else if let Some(bracket) = rest.strip_prefix('[') {
+            let end = bracket.find(']').ok_or_else(invalid)?;
+            token = bracket[..end].trim_matches(|c| c == '\'' || c == '"');
+            rest = &bracket[end + 1..];
+        } else if pointer.is_empty() {
+            // allow leaving out the leading `$.`
+            let end = rest.find(['.', '[']).unwrap_or(rest.len());
+            token = &rest[..end];
+            rest = &rest[end..];
+        } else {
+            return Err(invalid());
+        }
+
+        if token.is_empty() || token == "*" {
+            return Err(invalid());
+        }
+        pointer += &format!("/{}", token.replace('~', "~0").replace('/', "~1"));
+    }
+
+    Ok(pointer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_path_to_pointer() {
+        assert_eq!(path_to_pointer("$.db.status").unwrap(), "/db/status");
+        assert_eq!(
+            path_to_pointer("$.checks[0].status").unwrap(),
+            "/checks/0/status"
+        );
+        assert_eq!(path_to_pointer("$['db']['a/b']").unwrap(), "/db/a~1b");
+        assert_eq!(path_to_pointer("status").unwrap(), "/status");
+        assert_eq!(path_to_pointer("$").unwrap(), "");
+        assert!(path_to_pointer("$.checks[*]").is_err());
+        assert!(path_to_pointer("$.checks[0").is_err());
+    }
+
+    #[test]
+    fn test_parse() {
+        assert!(JsonAssertion::parse(&json!({"path": "$.status", "equals": "UP"})).is_ok());
+        assert!(JsonAssertion::parse(&json!({"pointer": "/status", "exists": true})).is_ok());
+        assert!(JsonAssertion::parse(&json!({"path": "$.status"})).is_err());
+        assert!(JsonAssertion::parse(&json!({"path": "$.n", "gt": 1, "lt": 5})).is_err());
+        assert!(JsonAssertion::parse(&json!({"equals": "UP"})).is_err());
+        assert!(JsonAssertion::parse(&json!({"path": "$.n", "gt": "one"})).is_err());
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let document = json!({"status": "UP", "db": {"status": "DOWN", "connections": 7}});
+        let check = |assertion: Value| {
+            JsonAssertion::parse(&assertion)
+                .unwrap()
+                .evaluate(&document)
+        };
+
+        assert!(check(json!({"path": "$.status", "equals": "UP"})).is_ok());
+        assert!(check(json!({"path": "$.db.status", "in": ["UP", "OK"]})).is_err());
+        assert!(check(json!({"pointer": "/db/connections", "gt": 5})).is_ok());
+        assert!(check(json!({"pointer": "/db/connections", "lt": 5})).is_err());
+        assert!(check(json!({"path": "$.cache", "exists": false})).is_ok());
+        assert!(check(json!({"path": "$.cache", "exists": true})).is_err());
+
+        let err = check(json!({"path": "$.db.status", "equals": "UP"})).unwrap_err();
+        assert_eq!(
+            err,
+            "Assertion failed: $.db.status equals \"UP\" (got \"DOWN\")"
+        );
+        let err = check(json!({"path": "$.missing", "equals": 1})).unwrap_err();
+        assert_eq!(err, "$.missing not found");
+    }
+}
@@ -1,10 +1,123 @@
+use std::time::Duration;
+
+use crate::healthcheck::probe::database::DatabaseLogin;
+use crate::healthcheck::probe::dns::DnsQuery;
+use crate::healthcheck::probe::exec::ExecCommand;
+use crate::healthcheck::probe::grpc::GrpcTarget;
+use crate::healthcheck::probe::redis::RedisCheck;
+use crate::healthcheck::probe::tls::CertPolicy;
+
+/// What kind of service a node is, and how to talk to it.
+#[derive(Debug, Clone)]
+pub enum NodeKind {
+    /// An HTTP endpoint, checked with the node's strategy.
+    Http,
+    /// A raw TCP service, healthy when it accepts a connection. If `send` is
+    /// set it is written once connected, and if `expect` is set the service
+    /// has to answer with a response starting with it.
+    Tcp {
+        send: Option<String>,
+        expect: Option<String>,
+    },
+    /// A TLS service, checked through its certificate.
+    Tls(CertPolicy),
+    /// A name resolved through a DNS server, checked through the records it
+    /// resolves to.
+    Dns(DnsQuery),
+    /// A gRPC server implementing the standard `grpc.health.v1.Health`
+    /// service.
+    Grpc(GrpcTarget),
+    /// A Postgres server, checked by logging in.
+    Postgres(DatabaseLogin),
+    /// A MySQL server, checked by logging in.
+    Mysql(DatabaseLogin),
+    /// A Redis server, checked with `PING` and optionally its replication
+    /// state.
+    Redis(RedisCheck),
+    /// A local command, checked through its exit code.
+    Exec(ExecCommand),
+    /// A job that pings healthcheckify instead of being probed, down when
+    /// no ping arrives within the node's interval plus `grace`.
+    Heartbeat { grace: Duration },
+}
+
 #[derive(Debug, Clone)]
 pub struct NodeConfig {
+    /// Where the node lives: a url for HTTP nodes, `host:port` for TCP, TLS,
+    /// gRPC, database and Redis ones, the resolved name for DNS ones, the
+    /// command line for exec ones and the ping path for heartbeats.
     pub url: String,
+    pub kind: NodeKind,
 }
 
 impl NodeConfig {
     pub fn new(url: String) -> Self {
-        Self { url }
+        Self {
+            url,
+            kind: NodeKind::Http,
+        }
+    }
+
+    pub fn tcp(address: String, send: Option<String>, expect: Option<String>) -> Self {
+        Self {
+            url: address,
+            kind: NodeKind::Tcp { send, expect },
+        }
+    }
+
+    pub fn tls(address: String, policy: CertPolicy) -> Self {
+        Self {
+            url: address,
+            kind: NodeKind::Tls(policy),
+        }
+    }
+
+    pub fn dns(query: DnsQuery) -> Self {
+        Self {
+            url: query.name.clone(),
+            kind: NodeKind::Dns(query),
+        }
+    }
+
+    pub fn grpc(address: String, target: GrpcTarget) -> Self {
+        Self {
+            url: address,
+            kind: NodeKind::Grpc(target),
+        }
+    }
+
+    pub fn postgres(address: String, login: DatabaseLogin) -> Self {
+        Self {
+            url: address,
+            kind: NodeKind::Postgres(login),
+        }
+    }
+
+    pub fn mysql(address: String, login: DatabaseLogin) -> Self {
+        Self {
+            url: address,
+            kind: NodeKind::Mysql(login),
+        }
+    }
+
+    pub fn redis(address: String, redis: RedisCheck) -> Self {
+        Self {
+            url: address,
+            kind: NodeKind::Redis(redis),
+        }
+    }
+
+    pub fn exec(command: ExecCommand) -> Self {
+        Self {
+            url: command.display(),
+            kind: NodeKind::Exec(command),
+        }
+    }
+
+    pub fn heartbeat(id: &str, grace: Duration) -> Self {
+        Self {
+            url: format!("/heartbeat/{}", id),
+            kind: NodeKind::Heartbeat { grace },
+        }
     }
 }
@@ -0,0 +1,144 @@
+use std::time::{Duration, SystemTime};
+
+use super::model::{CheckResult, NodeStatus};
+
+/// Longest message kept from a ping's body.
+const MAX_MESSAGE: usize = 1024;
+
+/// A ping sent by a job to a heartbeat node.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Ping {
+    /// The job started; its duration is reported once it finishes.
+    Start,
+    /// The job ran, or finished successfully.
+    Success,
+    /// The job failed.
+    Fail,
+}
+
+impl Ping {
+    pub fn parse(signal: &str) -> Option<Self> {
+        match signal {
+            "start" => Some(Ping::Start),
+            "success" => Some(Ping::Success),
+            "fail" => Some(Ping::Fail),
+            _ => None,
+        }
+    }
+}
+
+/// Pings received by a heartbeat node. The node goes down when no ping
+/// arrives within its interval plus `grace`.
+#[derive(Debug, Clone)]
+pub(crate) struct Heartbeat {
+    grace: Duration,
+    last_ping: SystemTime,
+    started: Option<SystemTime>,
+    /// Whether the missed deadline has already been recorded.
+    expired: bool,
+}
+
+impl Heartbeat {
+    /// Starts waiting for the first ping now.
+    pub(crate) fn new(grace: Duration) -> Self {
+        Self {
+            grace,
+            last_ping: SystemTime::now(),
+            started: None,
+            expired: false,
+        }
+    }
+
+    /// Takes a ping, returning the result to record. A start ping only
+    /// resets the deadline.
+    pub(crate) fn ping(&mut self, ping: Ping, message: Option<&str>) -> Option<CheckResult> {
+        let now = SystemTime::now();
+        self.last_ping = now;
+        self.expired = false;
+
+        let result = match ping {
+            Ping::Start => {
+                self.started = Some(now);
+                return None;
+            }
+            Ping::Success => CheckResult::new(NodeStatus::Healthy, None),
+            Ping::Fail => CheckResult::failed("Job reported a failure".to_string()),
+        };
+        let duration = self
+            .started
+            .take()
+            .and_then(|started| now.duration_since(started).ok());
+
+        Some(CheckResult {
+            latency: duration,
+            output: message.map(truncate).filter(|message| !message.is_empty()),
+            ..result
+        })
+    }
+
+    /// A down result the first time the deadline passes without a ping.
+    pub(crate) fn expire(&mut self, interval: Duration) -> Option<CheckResult> {
+        let silence = SystemTime::now().duration_since(self.last_ping).ok()?;
+        if self.expired || silence <= interval + self.grace {
+            return None;
+        }
+        self.expired = true;
+
+        let error = match self.started {
+            Some(_) => format!("Job started {}s ago and hasn't finished", silence.as_secs()),
+            None => format!("No ping for {}s", silence.as_secs()),
+        };
+        Some(CheckResult::failed(error))
+    }
+}
+
+fn truncate(message: &str) -> String {
+    let mut end = message.len().min(MAX_MESSAGE);
+    while !message.is_char_boundary(end) {
+        end -= 1;
+    }
+    message[..end].trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pings() {
+        let mut heartbeat = Heartbeat::new(Duration::from_secs(60));
+        assert!(heartbeat.ping(Ping::Start, None).is_none());
+        assert!(heartbeat.started.is_some());
+
+        let result = heartbeat.ping(Ping::Success, Some("42 rows\n")).unwrap();
+        assert_eq!(result.status, NodeStatus::Healthy);
+        assert!(result.latency.is_some());
+        assert_eq!(result.output.as_deref(), Some("42 rows"));
+
+        let result = heartbeat.ping(Ping::Fail, None).unwrap();
+        assert_eq!(result.status, NodeStatus::Down);
+        assert_eq!(result.latency, None);
+    }
+
+    #[test]
+    fn test_expire() {
+        let mut heartbeat = Heartbeat::new(Duration::from_secs(1));
+        assert!(heartbeat.expire(Duration::from_secs(10)).is_none());
+
+        heartbeat.last_ping = SystemTime::now() - Duration::from_secs(30);
+        let result = heartbeat.expire(Duration::from_secs(10)).unwrap();
+        assert_eq!(result.error.unwrap(), "No ping for 30s");
+        // only recorded once per missed deadline
+        assert!(heartbeat.expire(Duration::from_secs(10)).is_none());
+
+        heartbeat.ping(Ping::Start, None);
+        heartbeat.last_ping = SystemTime::now() - Duration::from_secs(30);
+        let result = heartbeat.expire(Duration::from_secs(10)).unwrap();
+        assert!(result.error.unwrap().starts_with("Job started 30s ago"));
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate(&"é".repeat(MAX_MESSAGE)).len(), MAX_MESSAGE);
+    }
+}
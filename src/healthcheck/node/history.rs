@@ -0,0 +1,172 @@
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime};
+
+use super::model::NodeStatus;
+
+/// Most transitions kept per node.
+const MAX_TRANSITIONS: usize = 32;
+
+/// How many results it takes for a node to change status, and how many
+/// changes make it flapping.
+#[derive(Debug, Clone, Copy)]
+pub struct Thresholds {
+    /// Consecutive successful checks for a down node to come back up.
+    pub rise: u32,
+    /// Consecutive failed checks for a node to go down.
+    pub fall: u32,
+    /// A node is flapping when it changed status `flap_threshold` times or
+    /// more within the last `flap_window`.
+    pub flap_window: Duration,
+    pub flap_threshold: usize,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self {
+            rise: 1,
+            fall: 1,
+            flap_window: Duration::from_secs(600),
+            flap_threshold: 5,
+        }
+    }
+}
+
+/// A change of a node's status.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transition {
+    pub at: SystemTime,
+    pub from: NodeStatus,
+    pub to: NodeStatus,
+}
+
+/// The latest status changes of a node, oldest first.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct History {
+    transitions: VecDeque<Transition>,
+}
+
+impl History {
+    pub(crate) fn push(&mut self, from: NodeStatus, to: NodeStatus) {
+        if self.transitions.len() == MAX_TRANSITIONS {
+            self.transitions.pop_front();
+        }
+        self.transitions.push_back(Transition {
+            at: SystemTime::now(),
+            from,
+            to,
+        });
+    }
+
+    pub(crate) fn transitions(&self) -> impl Iterator<Item = &Transition> {
+        self.transitions.iter()
+    }
+
+    pub(crate) fn is_flapping(&self, thresholds: &Thresholds) -> bool {
+        let since = match SystemTime::now().checked_sub(thresholds.flap_window) {
+            Some(since) => since,
+            None => return false,
+        };
+        let recent = self
+            .transitions
+            .iter()
+            .filter(|transition| transition.at >= since)
+            .count();
+        recent >= thresholds.flap_threshold
+    }
+}
+
+/// The status a node moves to after observing `observed`, given how many
+/// checks in a row succeeded or failed, counting this one. A node that
+/// hasn't been checked yet takes the first result as it is.
+pub(crate) fn next_status(
+    current: NodeStatus,
+    observed: NodeStatus,
+    successes: u32,
+    failures: u32,
+    thresholds: &Thresholds,
+) -> NodeStatus {
+    match (current, observed) {
+        (NodeStatus::Processing, observed) => observed,
+        (NodeStatus::Down, NodeStatus::Down) => NodeStatus::Down,
+        (NodeStatus::Down, observed) if successes >= thresholds.rise => observed,
+        (NodeStatus::Down, _) => NodeStatus::Down,
+        (current, NodeStatus::Down) if failures < thresholds.fall => current,
+        (_, observed) => observed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_status() {
+        let thresholds = Thresholds {
+            rise: 2,
+            fall: 3,
+            ..Thresholds::default()
+        };
+        let next = |current, observed, successes, failures| {
+            next_status(current, observed, successes, failures, &thresholds)
+        };
+
+        assert_eq!(
+            next(NodeStatus::Processing, NodeStatus::Down, 0, 1),
+            NodeStatus::Down
+        );
+        assert_eq!(
+            next(NodeStatus::Healthy, NodeStatus::Down, 0, 2),
+            NodeStatus::Healthy
+        );
+        assert_eq!(
+            next(NodeStatus::Degraded, NodeStatus::Down, 0, 2),
+            NodeStatus::Degraded
+        );
+        assert_eq!(
+            next(NodeStatus::Healthy, NodeStatus::Down, 0, 3),
+            NodeStatus::Down
+        );
+        assert_eq!(
+            next(NodeStatus::Down, NodeStatus::Healthy, 1, 0),
+            NodeStatus::Down
+        );
+        assert_eq!(
+            next(NodeStatus::Down, NodeStatus::Healthy, 2, 0),
+            NodeStatus::Healthy
+        );
+        assert_eq!(
+            next(NodeStatus::Healthy, NodeStatus::Degraded, 1, 0),
+            NodeStatus::Degraded
+        );
+    }
+
+    #[test]
+    fn test_flapping() {
+        let thresholds = Thresholds {
+            flap_threshold: 3,
+            ..Thresholds::default()
+        };
+        let mut history = History::default();
+        history.push(NodeStatus::Processing, NodeStatus::Healthy);
+        history.push(NodeStatus::Healthy, NodeStatus::Down);
+        assert!(!history.is_flapping(&thresholds));
+
+        history.push(NodeStatus::Down, NodeStatus::Healthy);
+        assert!(history.is_flapping(&thresholds));
+
+        // transitions older than the window don't count
+        for transition in history.transitions.iter_mut() {
+            transition.at -= Duration::from_secs(3600);
+        }
+        assert!(!history.is_flapping(&thresholds));
+    }
+
+    #[test]
+    fn test_bounded_history() {
+        let mut history = History::default();
+        for _ in 0..MAX_TRANSITIONS + 5 {
+            history.push(NodeStatus::Healthy, NodeStatus::Down);
+        }
+        assert_eq!(history.transitions().count(), MAX_TRANSITIONS);
+    }
+}
@@ -0,0 +1,46 @@
+use super::model::{CheckResult, NodeStatus};
+
+/// Upper bounds, in seconds, of the probe duration histogram buckets.
+pub const DURATION_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Counters accumulated over every check of a node since startup.
+#[derive(Debug, Clone, Default)]
+pub struct NodeMetrics {
+    pub checks_total: u64,
+    pub failures_total: u64,
+    /// Cumulative count of probes per `DURATION_BUCKETS` entry.
+    pub duration_buckets: [u64; DURATION_BUCKETS.len()],
+    pub duration_sum: f64,
+    pub duration_count: u64,
+    /// How long the last job reported to a heartbeat node ran, in seconds.
+    pub job_duration: Option<f64>,
+}
+
+impl NodeMetrics {
+    /// Counts `result`. The latency of a heartbeat node is the run time of
+    /// the job that pinged it, which is kept out of the probe histogram.
+    pub fn observe(&mut self, result: &CheckResult, heartbeat: bool) {
+        self.checks_total += 1;
+        if result.status == NodeStatus::Down {
+            self.failures_total += 1;
+        }
+
+        let Some(latency) = result.latency else {
+            return;
+        };
+        let seconds = latency.as_secs_f64();
+        if heartbeat {
+            self.job_duration = Some(seconds);
+            return;
+        }
+        for (bucket, bound) in self.duration_buckets.iter_mut().zip(DURATION_BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+        self.duration_sum += seconds;
+        self.duration_count += 1;
+    }
+}
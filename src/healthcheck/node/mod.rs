@@ -1,2 +1,7 @@
+pub mod assertion;
 pub mod config;
+pub mod heartbeat;
+pub mod history;
+pub mod metrics;
 pub mod model;
+pub mod retry;
@@ -1,21 +1,232 @@
 use crate::logger::log::{log, LogLevel};
 
-use super::config::NodeConfig;
+use super::assertion::JsonAssertion;
+use super::config::{NodeConfig, NodeKind};
+use super::heartbeat::{Heartbeat, Ping};
+use super::history::{next_status, History, Thresholds, Transition};
+use super::metrics::NodeMetrics;
+use super::retry::{is_transient_code, is_transient_error, RetryPolicy};
+use crate::healthcheck::probe::database::{mysql, postgres};
+use crate::healthcheck::probe::{dns, exec, grpc, redis, tcp, tls};
+use regex::Regex;
+use serde::Serialize;
 use std::error::Error;
-use std::time::{Duration, SystemTime};
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime};
 
-#[derive(PartialEq, Debug, Copy, Clone)]
+#[derive(PartialEq, Debug, Copy, Clone, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum NodeStatus {
     Healthy,
+    /// Up, but not working as well as it should, e.g. answering slowly.
+    Degraded,
     Processing,
     Down,
 }
 
+impl NodeStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NodeStatus::Healthy => "healthy",
+            NodeStatus::Degraded => "degraded",
+            NodeStatus::Processing => "processing",
+            NodeStatus::Down => "down",
+        }
+    }
+}
+
+/// What a single probe observed about a node.
+#[derive(Debug, Clone)]
+pub struct CheckResult {
+    pub status: NodeStatus,
+    pub checked_at: SystemTime,
+    pub latency: Option<Duration>,
+    pub http_code: Option<u16>,
+    /// Days until the node's TLS certificate expires.
+    pub cert_days_left: Option<i64>,
+    /// Why the node is not healthy, if it isn't.
+    pub error: Option<String>,
+    /// What the command printed, for exec nodes.
+    pub output: Option<String>,
+    /// Probes it took to get this result, retries included.
+    pub attempts: u32,
+}
+
+impl CheckResult {
+    pub(crate) fn new(status: NodeStatus, error: Option<String>) -> Self {
+        Self {
+            status,
+            checked_at: SystemTime::now(),
+            latency: None,
+            http_code: None,
+            cert_days_left: None,
+            error,
+            output: None,
+            attempts: 1,
+        }
+    }
+
+    /// A healthy result if there is no `error`, a down one otherwise.
+    pub(crate) fn from_error(error: Option<String>) -> Self {
+        match error {
+            Some(error) => Self::failed(error),
+            None => Self::new(NodeStatus::Healthy, None),
+        }
+    }
+
+    pub(crate) fn failed(error: String) -> Self {
+        Self::new(NodeStatus::Down, Some(error))
+    }
+}
+
+#[derive(Clone)]
 pub enum NodeCheckStrategy {
     BodyContains(String),
+    /// Healthy when the body matches the pattern, or when it doesn't if
+    /// `negate` is set.
+    BodyMatches {
+        regex: Regex,
+        negate: bool,
+    },
+    /// Healthy when the body is JSON and every assertion holds.
+    JsonAssertions(Vec<JsonAssertion>),
+    /// Healthy for any status code in `200..400`.
     StatusCode,
+    /// Healthy for any status code in one of the ranges.
+    ExpectedStatus(Vec<RangeInclusive<u16>>),
+    /// Healthy when the response arrives within `max`. Slower responses are
+    /// marked degraded if `degrade` is set, down otherwise.
+    MaxLatency {
+        max: Duration,
+        degrade: bool,
+    },
+    /// Healthy when every strategy passes.
+    All(Vec<NodeCheckStrategy>),
+    /// Healthy when at least one strategy passes.
+    Any(Vec<NodeCheckStrategy>),
+}
+
+/// The parts of a response that strategies look at.
+pub(crate) struct ProbeResponse {
+    pub http_code: u16,
+    pub body: String,
+    pub latency: Duration,
 }
 
+impl NodeCheckStrategy {
+    /// Whether evaluating the strategy requires the response body.
+    pub fn needs_body(&self) -> bool {
+        match self {
+            NodeCheckStrategy::BodyContains(_)
+            | NodeCheckStrategy::BodyMatches { .. }
+            | NodeCheckStrategy::JsonAssertions(_) => true,
+            NodeCheckStrategy::StatusCode
+            | NodeCheckStrategy::ExpectedStatus(_)
+            | NodeCheckStrategy::MaxLatency { .. } => false,
+            NodeCheckStrategy::All(strategies) | NodeCheckStrategy::Any(strategies) => {
+                strategies.iter().any(|strategy| strategy.needs_body())
+            }
+        }
+    }
+
+    /// Returns `None` if the response passes, or the status it earns along
+    /// with the reason.
+    pub(crate) fn evaluate(&self, response: &ProbeResponse) -> Option<(NodeStatus, String)> {
+        let down = |reason: String| Some((NodeStatus::Down, reason));
+        match self {
+            NodeCheckStrategy::StatusCode => {
+                if (200..400).contains(&response.http_code) {
+                    None
+                } else {
+                    down(format!("Unexpected status code: {}", response.http_code))
+                }
+            }
+            NodeCheckStrategy::ExpectedStatus(ranges) => {
+                if ranges
+                    .iter()
+                    .any(|range| range.contains(&response.http_code))
+                {
+                    None
+                } else {
+                    down(format!("Unexpected status code: {}", response.http_code))
+                }
+            }
+            NodeCheckStrategy::BodyContains(x) => {
+                if response.body.contains(x) {
+                    None
+                } else {
+                    down(format!("Response body does not contain '{}'", x))
+                }
+            }
+            NodeCheckStrategy::BodyMatches { regex, negate } => {
+                match (regex.is_match(&response.body), negate) {
+                    (true, false) | (false, true) => None,
+                    (false, false) => down(format!("Response body does not match /{}/", regex)),
+                    (true, true) => down(format!("Response body matches /{}/", regex)),
+                }
+            }
+            NodeCheckStrategy::JsonAssertions(assertions) => {
+                match serde_json::from_str::<serde_json::Value>(&response.body) {
+                    Ok(document) => assertions
+                        .iter()
+                        .find_map(|assertion| assertion.evaluate(&document).err())
+                        .and_then(down),
+                    Err(err) => down(format!("Response body is not valid JSON: {}", err)),
+                }
+            }
+            NodeCheckStrategy::MaxLatency { max, degrade } => {
+                if response.latency <= *max {
+                    return None;
+                }
+                let status = if *degrade {
+                    NodeStatus::Degraded
+                } else {
+                    NodeStatus::Down
+                };
+                Some((
+                    status,
+                    format!(
+                        "Response took {}ms, more than {}ms",
+                        response.latency.as_millis(),
+                        max.as_millis()
+                    ),
+                ))
+            }
+            NodeCheckStrategy::All(strategies) => {
+                let failures = strategies
+                    .iter()
+                    .filter_map(|strategy| strategy.evaluate(response));
+                // report the most severe failure
+                failures.fold(None, |worst, (status, reason)| match worst {
+                    Some((NodeStatus::Down, _)) => worst,
+                    _ => Some((status, reason)),
+                })
+            }
+            NodeCheckStrategy::Any(strategies) => {
+                let mut failures = Vec::with_capacity(strategies.len());
+                for strategy in strategies {
+                    match strategy.evaluate(response) {
+                        None => return None,
+                        Some(failure) => failures.push(failure),
+                    }
+                }
+                let status = if failures
+                    .iter()
+                    .any(|(status, _)| *status == NodeStatus::Degraded)
+                {
+                    NodeStatus::Degraded
+                } else {
+                    NodeStatus::Down
+                };
+                let reasons: Vec<String> = failures.into_iter().map(|(_, reason)| reason).collect();
+                Some((status, reasons.join("; ")))
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
 pub enum RequestMethod {
     POST,
     GET,
@@ -31,8 +242,22 @@ pub(crate) struct Node {
     method: RequestMethod,
     request_body: String,
     call_timeout: u64,
+    last_result: Option<CheckResult>,
+    consecutive_failures: u32,
+    consecutive_successes: u32,
+    thresholds: Thresholds,
+    retry: RetryPolicy,
+    history: History,
+    metrics: NodeMetrics,
+    /// Pings received, for heartbeat nodes.
+    heartbeat: Option<Heartbeat>,
+    /// Tells this node apart from every other one built in the process.
+    key: u64,
 }
 
+/// Key of the next node built.
+static NEXT_KEY: AtomicU64 = AtomicU64::new(0);
+
 impl Node {
     pub fn new(
         config: NodeConfig,
@@ -44,6 +269,10 @@ impl Node {
         call_timeout: u64,
     ) -> Self {
         let request_body = request_body.unwrap_or("".to_string());
+        let heartbeat = match config.kind {
+            NodeKind::Heartbeat { grace } => Some(Heartbeat::new(grace)),
+            _ => None,
+        };
         Self {
             id,
             config,
@@ -56,79 +285,333 @@ impl Node {
             method,
             request_body,
             call_timeout,
+            last_result: None,
+            consecutive_failures: 0,
+            consecutive_successes: 0,
+            thresholds: Thresholds::default(),
+            retry: RetryPolicy::default(),
+            history: History::default(),
+            metrics: NodeMetrics::default(),
+            heartbeat,
+            key: NEXT_KEY.fetch_add(1, Ordering::Relaxed),
         }
     }
 
+    /// Replaces the default rise, fall and flapping thresholds.
+    pub fn with_thresholds(mut self, thresholds: Thresholds) -> Self {
+        self.thresholds = thresholds;
+        self
+    }
+
+    /// Replaces the default policy of not retrying failed checks.
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
     pub fn status(&self) -> NodeStatus {
         self.status
     }
 
-    pub fn check(&mut self) -> Result<NodeStatus, Box<dyn Error>> {
+    pub fn url(&self) -> &str {
+        &self.config.url
+    }
+
+    /// Outcome of the most recent probe, `None` until the first one finishes.
+    pub fn last_result(&self) -> Option<&CheckResult> {
+        self.last_result.as_ref()
+    }
+
+    pub fn consecutive_failures(&self) -> u32 {
+        self.consecutive_failures
+    }
+
+    /// Whether the node changed status too often lately.
+    pub fn is_flapping(&self) -> bool {
+        self.history.is_flapping(&self.thresholds)
+    }
+
+    /// The latest status changes, oldest first.
+    pub fn transitions(&self) -> impl Iterator<Item = &Transition> {
+        self.history.transitions()
+    }
+
+    pub fn metrics(&self) -> &NodeMetrics {
+        &self.metrics
+    }
+
+    /// Whether the node waits for pings rather than being probed.
+    pub fn is_heartbeat(&self) -> bool {
+        self.heartbeat.is_some()
+    }
+
+    /// Identifies the node for as long as it lives, across reloads that keep
+    /// it, unlike its index in the checker.
+    pub(crate) fn key(&self) -> u64 {
+        self.key
+    }
+
+    /// Returns true once `interval` seconds have passed since the last check.
+    pub fn is_due(&self) -> bool {
+        match SystemTime::now().duration_since(self.last_check) {
+            Ok(elapsed) => elapsed.as_secs() >= self.interval,
+            Err(_) => false,
+        }
+    }
+
+    /// Marks the node as being checked and hands out the probe to run, or
+    /// `None` if the node was checked less than `interval` seconds ago.
+    /// Heartbeat nodes are never probed; they are marked down here once
+    /// their deadline passes.
+    pub(crate) fn start_check(&mut self) -> Option<Probe> {
+        if let Some(heartbeat) = self.heartbeat.as_mut() {
+            if let Some(result) = heartbeat.expire(Duration::from_secs(self.interval)) {
+                self.record(result);
+            }
+            return None;
+        }
+        if !self.is_due() {
+            return None;
+        }
+        self.last_check = SystemTime::now();
+
+        Some(Probe {
+            config: self.config.clone(),
+            strategy: self.strategy.clone(),
+            method: self.method.clone(),
+            request_body: self.request_body.clone(),
+            call_timeout: self.call_timeout,
+            retry: self.retry,
+        })
+    }
+
+    /// Stores the outcome of a probe handed out by `start_check`. The node
+    /// only changes status once its rise or fall threshold is reached.
+    pub(crate) fn record(&mut self, result: CheckResult) {
+        self.metrics.observe(&result, self.is_heartbeat());
+        if result.status == NodeStatus::Down {
+            self.consecutive_failures += 1;
+            self.consecutive_successes = 0;
+        } else {
+            self.consecutive_successes += 1;
+            self.consecutive_failures = 0;
+        }
+
+        let status = next_status(
+            self.status,
+            result.status,
+            self.consecutive_successes,
+            self.consecutive_failures,
+            &self.thresholds,
+        );
+        if status != self.status {
+            self.history.push(self.status, status);
+            let level = match status {
+                NodeStatus::Down => LogLevel::Error,
+                NodeStatus::Degraded => LogLevel::Warn,
+                _ => LogLevel::Info,
+            };
+            log(
+                format!(
+                    "{} ({}): {:?} -> {:?}",
+                    self.id, self.config.url, self.status, status
+                ),
+                level,
+            );
+        }
+        self.status = status;
+        self.last_result = Some(result);
         log(
-            format!("Checking url: '{}'", self.config.url),
-            LogLevel::Info,
+            format!("{} ({}): {:?}", self.id, self.config.url, self.status),
+            LogLevel::Debug,
         );
+    }
 
-        if self
-            .last_check
-            .duration_since(SystemTime::now())
-            .unwrap_err()
-            .duration()
-            .as_secs()
-            < self.interval
-        {
-            // check every 10 seconds
-            log("Returning cached status".to_string(), LogLevel::Info);
-            return Ok(self.status());
-        }
-        self.status = NodeStatus::Processing;
+    /// Takes a ping for a heartbeat node. Returns false if the node isn't
+    /// one.
+    pub(crate) fn ping(&mut self, ping: Ping, message: Option<&str>) -> bool {
+        let result = match self.heartbeat.as_mut() {
+            Some(heartbeat) => heartbeat.ping(ping, message),
+            None => return false,
+        };
         self.last_check = SystemTime::now();
+        if let Some(result) = result {
+            self.record(result);
+        }
+        true
+    }
+
+    /// Checks the node if it is due and returns its status, or the reason it
+    /// is down.
+    pub async fn check(
+        &mut self,
+        client: &reqwest::Client,
+    ) -> Result<NodeStatus, Box<dyn Error + Send + Sync>> {
         log(
-            format!("Sending request, timeout:{}", self.call_timeout),
+            format!("Checking url: '{}'", self.config.url),
             LogLevel::Info,
         );
-        let request = {
-            let client = reqwest::blocking::Client::builder()
-                .timeout(Duration::from_secs(self.call_timeout))
-                .build()?;
-            match self.method {
-                RequestMethod::GET => client.get(&self.config.url).send(),
-                RequestMethod::POST => client
-                    .post(&self.config.url)
-                    .body(self.request_body.clone())
-                    .send(),
+
+        let probe = match self.start_check() {
+            Some(probe) => probe,
+            None => {
+                log("Returning cached status".to_string(), LogLevel::Info);
+                return Ok(self.status());
             }
         };
 
-        log("Request sent".to_string(), LogLevel::Info);
-
-        if let Err(err) = request {
-            log(format!("Error: {}", err), LogLevel::Error);
-            self.status = NodeStatus::Down;
-            return Err(err.into());
+        let result = probe.run(client).await;
+        let error = result.error.clone();
+        self.record(result);
+        match error {
+            Some(error) if self.status == NodeStatus::Down => Err(error.into()),
+            _ => Ok(self.status),
         }
+    }
+}
 
-        let response = request.unwrap();
-        match &self.strategy {
-            NodeCheckStrategy::StatusCode => {
-                let status_code = response.status();
-                if (200..400).contains(&status_code.as_u16()) {
-                    self.status = NodeStatus::Healthy;
-                } else {
-                    self.status = NodeStatus::Down;
+/// A single health check request, detached from the node it belongs to so it
+/// can run without holding any lock on the node.
+#[derive(Clone)]
+pub(crate) struct Probe {
+    config: NodeConfig,
+    strategy: NodeCheckStrategy,
+    method: RequestMethod,
+    request_body: String,
+    call_timeout: u64,
+    retry: RetryPolicy,
+}
+
+impl Probe {
+    /// Checks the node according to its kind; for HTTP nodes, sends the
+    /// request on the shared `client` and evaluates the response against the
+    /// node's strategy. Probes that can't connect, time out, or get a 502,
+    /// 503 or 504, are retried according to the node's retry policy, and if
+    /// they still fail are reported as a `Down` result carrying the error.
+    pub async fn run(&self, client: &reqwest::Client) -> CheckResult {
+        let started = Instant::now();
+        let call_timeout = Duration::from_secs(self.call_timeout);
+        let deadline = started + self.retry.deadline(call_timeout);
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let (mut result, transient) =
+                match self.attempt(client, call_timeout.min(remaining)).await {
+                    Ok(result) => {
+                        let transient = result.status == NodeStatus::Down
+                            && is_transient_code(result.http_code);
+                        (result, transient)
+                    }
+                    Err(err) => {
+                        let transient = is_transient_error(err.as_ref());
+                        (CheckResult::failed(err.to_string()), transient)
+                    }
+                };
+
+            if transient && attempts <= self.retry.retries {
+                let delay = self.retry.delay(attempts);
+                if Instant::now() + delay < deadline {
+                    log(
+                        format!(
+                            "Retrying {} in {}ms: {}",
+                            self.config.url,
+                            delay.as_millis(),
+                            result.error.as_deref().unwrap_or_default()
+                        ),
+                        LogLevel::Info,
+                    );
+                    tokio::time::sleep(delay).await;
+                    continue;
                 }
             }
-            NodeCheckStrategy::BodyContains(x) => {
-                let body = response.text().unwrap();
-                if body.contains(x) {
-                    self.status = NodeStatus::Healthy;
-                } else {
-                    self.status = NodeStatus::Down;
-                }
+
+            result.latency = Some(started.elapsed());
+            result.attempts = attempts;
+            return result;
+        }
+    }
+
+    async fn attempt(
+        &self,
+        client: &reqwest::Client,
+        call_timeout: Duration,
+    ) -> Result<CheckResult, Box<dyn Error + Send + Sync>> {
+        match &self.config.kind {
+            NodeKind::Http => self.send(client, call_timeout).await,
+            NodeKind::Tcp { send, expect } => {
+                tcp::check(
+                    &self.config.url,
+                    send.as_deref(),
+                    expect.as_deref(),
+                    call_timeout,
+                )
+                .await
+            }
+            NodeKind::Tls(policy) => tls::check(&self.config.url, policy, call_timeout).await,
+            NodeKind::Dns(query) => dns::check(query, call_timeout).await,
+            NodeKind::Grpc(target) => grpc::check(&self.config.url, target, call_timeout).await,
+            NodeKind::Postgres(login) => {
+                postgres::check(&self.config.url, login, call_timeout).await
             }
+            NodeKind::Mysql(login) => mysql::check(&self.config.url, login, call_timeout).await,
+            NodeKind::Redis(check) => redis::check(&self.config.url, check, call_timeout).await,
+            NodeKind::Exec(command) => exec::check(command, call_timeout).await,
+            NodeKind::Heartbeat { .. } => Err("Heartbeat nodes are pinged, not probed".into()),
+        }
+    }
+
+    async fn send(
+        &self,
+        client: &reqwest::Client,
+        call_timeout: Duration,
+    ) -> Result<CheckResult, Box<dyn Error + Send + Sync>> {
+        log(
+            format!("Sending request, timeout:{}", call_timeout.as_secs_f64()),
+            LogLevel::Info,
+        );
+        let started = Instant::now();
+        let request = match self.method {
+            RequestMethod::GET => client.get(&self.config.url),
+            RequestMethod::POST => client
+                .post(&self.config.url)
+                .body(self.request_body.clone()),
         }
+        .timeout(call_timeout)
+        .send()
+        .await;
+
+        log("Request sent".to_string(), LogLevel::Info);
+
+        let response = match request {
+            Ok(response) => response,
+            Err(err) => {
+                log(format!("Error: {}", err), LogLevel::Error);
+                return Err(err.into());
+            }
+        };
+
+        let http_code = response.status().as_u16();
+        let body = if self.strategy.needs_body() {
+            response.text().await?
+        } else {
+            String::new()
+        };
+        let verdict = self.strategy.evaluate(&ProbeResponse {
+            http_code,
+            body,
+            latency: started.elapsed(),
+        });
+
+        let (status, error) = match verdict {
+            Some((status, reason)) => (status, Some(reason)),
+            None => (NodeStatus::Healthy, None),
+        };
 
-        Ok(self.status())
+        Ok(CheckResult {
+            http_code: Some(http_code),
+            ..CheckResult::new(status, error)
+        })
     }
 }
 
@@ -136,8 +619,8 @@ impl Node {
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_post_statuscode() {
+    #[tokio::test]
+    async fn test_post_statuscode() {
         let node_config = NodeConfig::new("https://httpbin.org/post".to_string());
         let mut node = Node::new(
             node_config,
@@ -150,12 +633,12 @@ mod tests {
         );
 
         assert_eq!(node.status, NodeStatus::Processing);
-        let _ = node.check().unwrap();
+        let _ = node.check(&reqwest::Client::new()).await.unwrap();
         assert_eq!(node.status, NodeStatus::Healthy);
     }
 
-    #[test]
-    fn test_post_contains() {
+    #[tokio::test]
+    async fn test_post_contains() {
         let node_config = NodeConfig::new("https://httpbin.org/post".to_string());
         let mut node = Node::new(
             node_config,
@@ -168,12 +651,12 @@ mod tests {
         );
 
         assert_eq!(node.status, NodeStatus::Processing);
-        let _ = node.check().unwrap();
+        let _ = node.check(&reqwest::Client::new()).await.unwrap();
         assert_eq!(node.status, NodeStatus::Healthy);
     }
 
-    #[test]
-    fn test_check_success() {
+    #[tokio::test]
+    async fn test_check_success() {
         let node_config = NodeConfig::new("https://google.com".to_string());
         let mut node = Node::new(
             node_config,
@@ -186,11 +669,11 @@ mod tests {
         );
 
         assert_eq!(node.status, NodeStatus::Processing);
-        let _ = node.check().unwrap();
+        let _ = node.check(&reqwest::Client::new()).await.unwrap();
         assert_eq!(node.status, NodeStatus::Healthy);
     }
-    #[test]
-    fn test_check_down() {
+    #[tokio::test]
+    async fn test_check_down() {
         let node_config = NodeConfig::new("https://thiswebsitedoesntexists.xcxc".to_string());
         let mut node = Node::new(
             node_config,
@@ -203,11 +686,12 @@ mod tests {
         );
 
         assert_eq!(node.status, NodeStatus::Processing);
-        let _ = node.check();
+        let _ = node.check(&reqwest::Client::new()).await;
         assert_eq!(node.status, NodeStatus::Down);
     }
-    #[test]
-    fn test_check_with_high_timeout() {
+
+    #[tokio::test]
+    async fn test_check_with_high_timeout() {
         let node_config = NodeConfig::new("https://httpbin.org/delay/2".to_string());
         let mut node = Node::new(
             node_config,
@@ -220,12 +704,12 @@ mod tests {
         );
 
         assert_eq!(node.status, NodeStatus::Processing);
-        let _ = node.check();
+        let _ = node.check(&reqwest::Client::new()).await;
         assert_eq!(node.status, NodeStatus::Healthy);
     }
 
-    #[test]
-    fn test_timeout() {
+    #[tokio::test]
+    async fn test_timeout() {
         let node_config = NodeConfig::new("https://httpbin.org/delay/2".to_string());
         let mut node = Node::new(
             node_config,
@@ -238,12 +722,12 @@ mod tests {
         );
 
         assert_eq!(node.status, NodeStatus::Processing);
-        let _ = node.check();
+        let _ = node.check(&reqwest::Client::new()).await;
         assert_eq!(node.status, NodeStatus::Down);
     }
 
-    #[test]
-    fn test_timeout_post() {
+    #[tokio::test]
+    async fn test_timeout_post() {
         let node_config = NodeConfig::new("https://httpbin.org/delay/5".to_string());
         let mut node = Node::new(
             node_config,
@@ -256,7 +740,93 @@ mod tests {
         );
 
         assert_eq!(node.status, NodeStatus::Processing);
-        let _ = node.check();
+        let _ = node.check(&reqwest::Client::new()).await;
         assert_eq!(node.status, NodeStatus::Healthy);
     }
+
+    #[test]
+    fn test_rise_fall_and_flapping() {
+        let mut node = Node::new(
+            NodeConfig::new("http://127.0.0.1:1/".to_string()),
+            "flaky".to_string(),
+            NodeCheckStrategy::StatusCode,
+            10,
+            RequestMethod::GET,
+            None,
+            30,
+        )
+        .with_thresholds(Thresholds {
+            rise: 2,
+            fall: 2,
+            flap_window: Duration::from_secs(600),
+            flap_threshold: 3,
+        });
+        let up = || CheckResult::new(NodeStatus::Healthy, None);
+        let down = || CheckResult::failed("Connection refused".to_string());
+
+        node.record(up());
+        node.record(down());
+        assert_eq!(node.status(), NodeStatus::Healthy);
+        assert_eq!(node.consecutive_failures(), 1);
+        node.record(down());
+        assert_eq!(node.status(), NodeStatus::Down);
+        node.record(up());
+        assert_eq!(node.status(), NodeStatus::Down);
+        assert!(!node.is_flapping());
+        node.record(up());
+        assert_eq!(node.status(), NodeStatus::Healthy);
+        assert!(node.is_flapping());
+
+        let transitions: Vec<(NodeStatus, NodeStatus)> = node
+            .transitions()
+            .map(|transition| (transition.from, transition.to))
+            .collect();
+        assert_eq!(
+            transitions,
+            vec![
+                (NodeStatus::Processing, NodeStatus::Healthy),
+                (NodeStatus::Healthy, NodeStatus::Down),
+                (NodeStatus::Down, NodeStatus::Healthy),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retry_transient() {
+        let url = crate::healthcheck::testing::serve(
+            "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        );
+        let node = |retry| {
+            Node::new(
+                NodeConfig::new(url.clone()),
+                "busy".to_string(),
+                NodeCheckStrategy::StatusCode,
+                10,
+                RequestMethod::GET,
+                None,
+                5,
+            )
+            .with_retry(retry)
+        };
+
+        let mut retried = node(RetryPolicy {
+            retries: 2,
+            backoff: Duration::from_millis(10),
+            deadline: None,
+        });
+        let _ = retried.check(&reqwest::Client::new()).await;
+        let result = retried.last_result().unwrap();
+        assert_eq!(retried.status(), NodeStatus::Down);
+        assert_eq!(result.http_code, Some(503));
+        assert_eq!(result.attempts, 3);
+
+        // A deadline too short for the first backoff leaves a single attempt.
+        let mut bounded = node(RetryPolicy {
+            retries: 2,
+            backoff: Duration::from_secs(5),
+            deadline: Some(Duration::from_secs(1)),
+        });
+        let _ = bounded.check(&reqwest::Client::new()).await;
+        assert_eq!(bounded.last_result().unwrap().attempts, 1);
+    }
 }
@@ -0,0 +1,164 @@
+use std::error::Error;
+use std::io;
+use std::time::Duration;
+
+use rand::Rng;
+
+use crate::healthcheck::probe::TimedOut;
+
+/// Longest wait between two attempts, however many retries came before.
+const MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+/// How a check retries transient failures before reporting them.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Attempts made after the first one, at most.
+    pub retries: u32,
+    /// Wait before the first retry, doubled for each one after it.
+    pub backoff: Duration,
+    /// Bound on the whole check, retries included. Defaults to enough time
+    /// for every attempt to take the node's call timeout, plus the longest
+    /// backoff between them.
+    pub deadline: Option<Duration>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retries: 0,
+            backoff: Duration::from_millis(200),
+            deadline: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Wait before retry number `retry`, starting at 1: between half and
+    /// all of the exponential backoff, so that nodes failing together don't
+    /// retry in lockstep.
+    pub(crate) fn delay(&self, retry: u32) -> Duration {
+        let half = self.backoff(retry) / 2;
+        half + half.mul_f64(rand::thread_rng().gen::<f64>())
+    }
+
+    /// Bound on a whole check whose attempts each take up to `call_timeout`.
+    pub(crate) fn deadline(&self, call_timeout: Duration) -> Duration {
+        self.deadline.unwrap_or_else(|| {
+            (1..=self.retries).fold(call_timeout, |deadline, retry| {
+                deadline
+                    .saturating_add(call_timeout)
+                    .saturating_add(self.backoff(retry))
+            })
+        })
+    }
+
+    /// Longest wait before retry number `retry`.
+    fn backoff(&self, retry: u32) -> Duration {
+        let exponent = retry.saturating_sub(1).min(16);
+        self.backoff.saturating_mul(1 << exponent).min(MAX_BACKOFF)
+    }
+}
+
+/// Whether a response means the target is only temporarily unavailable.
+pub(crate) fn is_transient_code(http_code: Option<u16>) -> bool {
+    matches!(http_code, Some(502..=504))
+}
+
+/// Whether a probe error means the target could be reached on another try:
+/// a refused or dropped connection, or a timeout. Anything else, like a name
+/// that doesn't resolve or a bad certificate, fails the same way every time.
+pub(crate) fn is_transient_error(err: &(dyn Error + 'static)) -> bool {
+    let mut source = Some(err);
+    while let Some(err) = source {
+        if err.is::<TimedOut>() {
+            return true;
+        }
+        if let Some(err) = err.downcast_ref::<reqwest::Error>() {
+            if err.is_timeout() {
+                return true;
+            }
+        }
+        if let Some(err) = err.downcast_ref::<io::Error>() {
+            return matches!(
+                err.kind(),
+                io::ErrorKind::ConnectionRefused
+                    | io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::TimedOut
+            );
+        }
+        source = err.source();
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delay() {
+        let policy = RetryPolicy {
+            retries: 10,
+            backoff: Duration::from_millis(100),
+            deadline: None,
+        };
+        for (retry, max) in [(1, 100), (2, 200), (3, 400)] {
+            let delay = policy.delay(retry);
+            assert!(delay >= Duration::from_millis(max / 2), "{:?}", delay);
+            assert!(delay <= Duration::from_millis(max), "{:?}", delay);
+        }
+        assert!(policy.delay(40) <= MAX_BACKOFF);
+    }
+
+    #[test]
+    fn test_deadline() {
+        let mut policy = RetryPolicy {
+            retries: 3,
+            backoff: Duration::from_secs(2),
+            deadline: None,
+        };
+        // three retries after 2s, 4s and 8s
+        let deadline = policy.deadline(Duration::from_secs(5));
+        assert_eq!(deadline, Duration::from_secs(5 * 4 + 2 + 4 + 8));
+        policy.retries = 0;
+        assert_eq!(
+            policy.deadline(Duration::from_secs(5)),
+            Duration::from_secs(5)
+        );
+        policy.deadline = Some(Duration::from_secs(1));
+        assert_eq!(
+            policy.deadline(Duration::from_secs(5)),
+            Duration::from_secs(1)
+        );
+    }
+
+    #[test]
+    fn test_transient_codes() {
+        assert!(is_transient_code(Some(503)));
+        assert!(!is_transient_code(Some(500)));
+        assert!(!is_transient_code(None));
+    }
+
+    #[tokio::test]
+    async fn test_transient_errors() {
+        let timed_out: Box<dyn Error + Send + Sync> = TimedOut(Duration::from_secs(1)).into();
+        assert!(is_transient_error(timed_out.as_ref()));
+        let refused = io::Error::from(io::ErrorKind::ConnectionRefused);
+        assert!(is_transient_error(&refused));
+        let missing = io::Error::from(io::ErrorKind::NotFound);
+        assert!(!is_transient_error(&missing));
+        let message: Box<dyn Error + Send + Sync> = "Invalid server public key".into();
+        assert!(!is_transient_error(message.as_ref()));
+
+        let client = reqwest::Client::new();
+        let refused = client.get("http://127.0.0.1:1/").send().await.unwrap_err();
+        assert!(is_transient_error(&refused));
+        let unresolved = client
+            .get("http://healthcheckify.invalid/")
+            .send()
+            .await
+            .unwrap_err();
+        assert!(!is_transient_error(&unresolved));
+    }
+}
@@ -1,36 +1,641 @@
-use serde_json::{Result, Value};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
 
-pub(crate) fn parse_config(config_str: String) -> Result<Vec<Value>> {
-    let configs: Vec<Value> = serde_json::from_str(&config_str)?;
+use serde::{
+    de::{self, DeserializeOwned},
+    Deserialize, Deserializer,
+};
+use serde_json::{Number, Value};
 
-    Ok(configs)
+use crate::healthcheck::group::GroupPolicy;
+
+/// One entry of the config file: all the services checked under an id.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GroupConfig {
+    pub id: String,
+    #[serde(default)]
+    pub policy: GroupPolicy,
+    #[serde(default)]
+    pub degraded_on_failure: bool,
+    #[serde(default = "default_degraded_code")]
+    pub degraded_code: u16,
+    pub services: Vec<ServiceConfig>,
+}
+
+fn default_degraded_code() -> u16 {
+    200
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ServiceType {
+    #[default]
+    Http,
+    Tcp,
+    Tls,
+    Dns,
+    Grpc,
+    Postgres,
+    Mysql,
+    Redis,
+    Exec,
+    Heartbeat,
+}
+
+impl ServiceType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ServiceType::Http => "http",
+            ServiceType::Tcp => "tcp",
+            ServiceType::Tls => "tls",
+            ServiceType::Dns => "dns",
+            ServiceType::Grpc => "grpc",
+            ServiceType::Postgres => "postgres",
+            ServiceType::Mysql => "mysql",
+            ServiceType::Redis => "redis",
+            ServiceType::Exec => "exec",
+            ServiceType::Heartbeat => "heartbeat",
+        }
+    }
+
+    /// The fields of this type, on top of the ones every type takes.
+    fn fields(&self) -> &'static [&'static str] {
+        match self {
+            ServiceType::Http => &[
+                "url",
+                "method",
+                "requestBody",
+                "strategy",
+                "strategy_string",
+                "negate",
+                "expected_status",
+                "assertions",
+                "max_latency_ms",
+                "degraded",
+                "conditions",
+                "match",
+            ],
+            ServiceType::Tcp => &["address", "send", "expect"],
+            ServiceType::Tls => &["address", "server_name", "ca_file", "min_days", "warn_days"],
+            ServiceType::Dns => &[
+                "name",
+                "record_type",
+                "resolver",
+                "expected",
+                "max_latency_ms",
+            ],
+            ServiceType::Grpc => &["address", "service", "tls", "server_name", "ca_file"],
+            ServiceType::Postgres => &[
+                "address",
+                "user",
+                "password",
+                "password_env",
+                "database",
+                "query",
+                "expect",
+                "tls",
+                "ca_file",
+            ],
+            ServiceType::Mysql => &[
+                "address",
+                "user",
+                "password",
+                "password_env",
+                "database",
+                "query",
+                "expect",
+            ],
+            ServiceType::Redis => &[
+                "address",
+                "user",
+                "password",
+                "password_env",
+                "role",
+                "max_lag_seconds",
+            ],
+            ServiceType::Exec => &["command", "args", "env"],
+            ServiceType::Heartbeat => &["grace"],
+        }
+    }
+}
+
+/// Fields every `type` takes.
+const COMMON_FIELDS: [&str; 7] = [
+    "type",
+    "interval",
+    "weight",
+    "rise",
+    "fall",
+    "flap_window",
+    "flap_threshold",
+];
+
+/// Fields of the services that are probed, i.e. every `type` but heartbeat.
+const PROBE_FIELDS: [&str; 4] = [
+    "call_timeout",
+    "retries",
+    "retry_backoff_ms",
+    "retry_deadline",
+];
+
+/// Fields that pick a single strategy, which `conditions` replaces.
+const STRATEGY_FIELDS: [&str; 7] = [
+    "strategy",
+    "strategy_string",
+    "negate",
+    "expected_status",
+    "assertions",
+    "max_latency_ms",
+    "degraded",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StrategyKind {
+    #[default]
+    StatusCode,
+    StringContains,
+    Regex,
+    Json,
+    Latency,
+}
+
+impl StrategyKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StrategyKind::StatusCode => "statuscode",
+            StrategyKind::StringContains => "stringcontains",
+            StrategyKind::Regex => "regex",
+            StrategyKind::Json => "json",
+            StrategyKind::Latency => "latency",
+        }
+    }
+}
+
+/// How the entries of `conditions` are combined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchMode {
+    #[default]
+    All,
+    Any,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Method {
+    #[default]
+    Get,
+    Post,
+}
+
+/// Methods are read regardless of case, `post` and `Post` alike.
+impl<'de> Deserialize<'de> for Method {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let method = String::deserialize(deserializer)?;
+        match method.to_ascii_uppercase().as_str() {
+            "GET" => Ok(Method::Get),
+            "POST" => Ok(Method::Post),
+            _ => Err(de::Error::invalid_value(
+                de::Unexpected::Str(&method),
+                &"GET or POST",
+            )),
+        }
+    }
+}
+
+/// `expected_status`: a code (`200`), a range (`"200-299"`) or a list of
+/// either.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum ExpectedStatus {
+    Code(u16),
+    Range(String),
+    List(Vec<ExpectedStatus>),
+}
+
+/// A command line argument or environment value, which may be written as a
+/// number or a boolean too.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Scalar {
+    String(String),
+    Number(Number),
+    Bool(bool),
+}
+
+impl fmt::Display for Scalar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Scalar::String(value) => write!(f, "{}", value),
+            Scalar::Number(value) => write!(f, "{}", value),
+            Scalar::Bool(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// How the response of an HTTP service is judged: the strategy fields of a
+/// service, or one entry of its `conditions`.
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StrategyConfig {
+    pub strategy: StrategyKind,
+    pub strategy_string: Option<String>,
+    pub negate: bool,
+    pub expected_status: Option<ExpectedStatus>,
+    /// Read with `JsonAssertion::parse`.
+    pub assertions: Option<Vec<Value>>,
+    pub max_latency_ms: Option<u64>,
+    pub degraded: bool,
+    pub conditions: Option<Vec<StrategyConfig>>,
+    #[serde(rename = "match")]
+    pub match_mode: MatchMode,
+}
+
+/// One service of a group. Every `type` shares this struct; the fields a
+/// type doesn't use are left to their defaults.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServiceConfig {
+    #[serde(rename = "type")]
+    pub service_type: ServiceType,
+    pub interval: u64,
+    pub call_timeout: u64,
+    pub weight: u32,
+    pub rise: u32,
+    pub fall: u32,
+    pub flap_window: u64,
+    pub flap_threshold: usize,
+    pub retries: u32,
+    pub retry_backoff_ms: u64,
+    pub retry_deadline: Option<u64>,
+
+    // http
+    pub url: Option<String>,
+    pub method: Method,
+    #[serde(rename = "requestBody")]
+    pub request_body: Option<String>,
+    pub strategy: StrategyKind,
+    pub strategy_string: Option<String>,
+    pub negate: bool,
+    pub expected_status: Option<ExpectedStatus>,
+    pub assertions: Option<Vec<Value>>,
+    /// The latency strategy's limit for HTTP services, the resolution's for
+    /// DNS ones.
+    pub max_latency_ms: Option<u64>,
+    pub degraded: bool,
+    pub conditions: Option<Vec<StrategyConfig>>,
+    #[serde(rename = "match")]
+    pub match_mode: MatchMode,
+
+    // tcp, tls, grpc, postgres, mysql and redis
+    pub address: Option<String>,
+    pub send: Option<String>,
+    pub expect: Option<String>,
+    pub server_name: Option<String>,
+    pub ca_file: Option<String>,
+    pub min_days: i64,
+    pub warn_days: Option<i64>,
+    pub service: Option<String>,
+    pub tls: bool,
+    pub user: Option<String>,
+    pub password: Option<String>,
+    pub password_env: Option<String>,
+    pub database: Option<String>,
+    pub query: Option<String>,
+    pub role: Option<String>,
+    pub max_lag_seconds: Option<u64>,
+
+    // dns
+    pub name: Option<String>,
+    pub record_type: Option<String>,
+    pub resolver: Option<String>,
+    pub expected: Vec<String>,
+
+    // exec
+    pub command: Option<String>,
+    pub args: Vec<Scalar>,
+    pub env: BTreeMap<String, Scalar>,
+
+    // heartbeat
+    pub grace: u64,
+}
+
+impl Default for ServiceConfig {
+    fn default() -> Self {
+        Self {
+            service_type: ServiceType::Http,
+            interval: 10,
+            call_timeout: 30,
+            weight: 1,
+            rise: 1,
+            fall: 1,
+            flap_window: 600,
+            flap_threshold: 5,
+            retries: 0,
+            retry_backoff_ms: 200,
+            retry_deadline: None,
+            url: None,
+            method: Method::Get,
+            request_body: None,
+            strategy: StrategyKind::StatusCode,
+            strategy_string: None,
+            negate: false,
+            expected_status: None,
+            assertions: None,
+            max_latency_ms: None,
+            degraded: false,
+            conditions: None,
+            match_mode: MatchMode::All,
+            address: None,
+            send: None,
+            expect: None,
+            server_name: None,
+            ca_file: None,
+            min_days: 0,
+            warn_days: None,
+            service: None,
+            tls: false,
+            user: None,
+            password: None,
+            password_env: None,
+            database: None,
+            query: None,
+            role: None,
+            max_lag_seconds: None,
+            name: None,
+            record_type: None,
+            resolver: None,
+            expected: Vec::new(),
+            command: None,
+            args: Vec::new(),
+            env: BTreeMap::new(),
+            grace: 60,
+        }
+    }
+}
+
+impl ServiceConfig {
+    /// The strategy fields of the service.
+    pub fn strategy(&self) -> StrategyConfig {
+        StrategyConfig {
+            strategy: self.strategy,
+            strategy_string: self.strategy_string.clone(),
+            negate: self.negate,
+            expected_status: self.expected_status.clone(),
+            assertions: self.assertions.clone(),
+            max_latency_ms: self.max_latency_ms,
+            degraded: self.degraded,
+            conditions: self.conditions.clone(),
+            match_mode: self.match_mode,
+        }
+    }
+}
+
+/// Everything wrong with a config, one `path: message` per problem, e.g.
+/// `[1].services[0].strategy_string: required when strategy=stringcontains`.
+#[derive(Debug, Default)]
+pub struct ConfigError {
+    errors: Vec<String>,
+}
+
+impl ConfigError {
+    pub(crate) fn push(&mut self, path: &str, message: impl fmt::Display) {
+        if path.is_empty() {
+            self.errors.push(message.to_string());
+        } else {
+            self.errors.push(format!("{}: {}", path, message));
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    pub fn errors(&self) -> &[String] {
+        &self.errors
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.errors.join("\n"))
+    }
+}
+
+impl Error for ConfigError {}
+
+/// The languages a config file can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Json,
+    Yaml,
+    /// An array of `[[group]]` tables, since a TOML document can't be an
+    /// array itself.
+    Toml,
+}
+
+/// Top level of a TOML config file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TomlDocument {
+    #[serde(default)]
+    group: Vec<Value>,
+}
+
+impl ConfigFormat {
+    /// Tells the format of a file from its extension: YAML for `.yaml` and
+    /// `.yml`, TOML for `.toml` and JSON for anything else.
+    pub fn from_path(path: &str) -> Self {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("yaml" | "yml") => ConfigFormat::Yaml,
+            Some("toml") => ConfigFormat::Toml,
+            _ => ConfigFormat::Json,
+        }
+    }
+
+    /// Reads the entries of a config file, whatever its format, into the
+    /// JSON values they are checked and parsed from.
+    fn entries(&self, config_str: &str) -> Result<Vec<Value>, String> {
+        match self {
+            ConfigFormat::Json => serde_json::from_str(config_str).map_err(|err| err.to_string()),
+            ConfigFormat::Yaml => {
+                let mut document: serde_yaml::Value =
+                    serde_yaml::from_str(config_str).map_err(|err| err.to_string())?;
+                // resolve `<<: *anchor` keys, for services sharing settings
+                document.apply_merge().map_err(|err| err.to_string())?;
+                serde_json::to_value(document)
+                    .and_then(serde_json::from_value)
+                    .map_err(|err| err.to_string())
+            }
+            ConfigFormat::Toml => toml::from_str::<TomlDocument>(config_str)
+                .map(|document| document.group)
+                .map_err(|err| err.to_string()),
+        }
+    }
+}
+
+/// Reads a config file. Each group and each service is read on its own, so
+/// that every malformed one is reported rather than the first.
+pub(crate) fn parse_config(
+    config_str: &str,
+    format: ConfigFormat,
+) -> Result<Vec<GroupConfig>, ConfigError> {
+    let mut errors = ConfigError::default();
+    let entries = match format.entries(config_str) {
+        Ok(entries) => entries,
+        Err(err) => {
+            errors.push("", err);
+            return Err(errors);
+        }
+    };
+
+    let mut groups = Vec::with_capacity(entries.len());
+    for (i, mut entry) in entries.into_iter().enumerate() {
+        let path = format!("[{}]", i);
+        let services = match entry.get_mut("services") {
+            Some(services) if services.is_array() => {
+                std::mem::replace(services, Value::Array(Vec::new()))
+            }
+            _ => Value::Null,
+        };
+        if services
+            .as_array()
+            .is_some_and(|services| services.is_empty())
+        {
+            errors.push(&format!("{}.services", path), "must not be empty");
+        }
+
+        let group: Option<GroupConfig> = deserialize(entry, &path, &mut errors);
+        let services: Vec<ServiceConfig> = match services {
+            Value::Array(services) => services
+                .into_iter()
+                .enumerate()
+                .filter_map(|(j, value)| {
+                    let path = format!("{}.services[{}]", path, j);
+                    let service: ServiceConfig = deserialize(value.clone(), &path, &mut errors)?;
+                    check_fields(service.service_type, &value, &path, &mut errors);
+                    if service.service_type == ServiceType::Http {
+                        check_strategy_fields(&value, &path, &mut errors);
+                    }
+                    Some(service)
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        if let Some(mut group) = group {
+            group.services = services;
+            groups.push(group);
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(groups)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Records every field of a service that its `type` doesn't take.
+fn check_fields(service_type: ServiceType, service: &Value, path: &str, errors: &mut ConfigError) {
+    let Some(fields) = service.as_object() else {
+        return;
+    };
+    for field in fields.keys() {
+        let allowed = COMMON_FIELDS.contains(&field.as_str())
+            || (service_type != ServiceType::Heartbeat && PROBE_FIELDS.contains(&field.as_str()))
+            || service_type.fields().contains(&field.as_str());
+        if !allowed {
+            errors.push(
+                &format!("{}.{}", path, field),
+                format!("not allowed when type={}", service_type.as_str()),
+            );
+        }
+    }
+}
+
+/// Records the strategy fields given alongside `conditions`, which would be
+/// ignored, and a `match` without `conditions` to combine, in a service or
+/// any of its conditions.
+fn check_strategy_fields(config: &Value, path: &str, errors: &mut ConfigError) {
+    let Some(conditions) = config.get("conditions") else {
+        if config.get("match").is_some() {
+            errors.push(
+                &format!("{}.match", path),
+                "only allowed together with conditions",
+            );
+        }
+        return;
+    };
+    for field in STRATEGY_FIELDS {
+        if config.get(field).is_some() {
+            errors.push(
+                &format!("{}.{}", path, field),
+                "not allowed together with conditions",
+            );
+        }
+    }
+    for (k, condition) in conditions.as_array().into_iter().flatten().enumerate() {
+        check_strategy_fields(condition, &format!("{}.conditions[{}]", path, k), errors);
+    }
+}
+
+fn deserialize<T: DeserializeOwned>(
+    value: Value,
+    path: &str,
+    errors: &mut ConfigError,
+) -> Option<T> {
+    match serde_path_to_error::deserialize(value) {
+        Ok(value) => Some(value),
+        Err(err) => {
+            let inner = err.path().to_string();
+            let path = match inner.as_str() {
+                "." => path.to_string(),
+                _ if inner.starts_with('[') => format!("{}{}", path, inner),
+                _ => format!("{}.{}", path, inner),
+            };
+            errors.push(&path, err.into_inner());
+            None
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::parse_config;
+    use super::*;
 
     #[test]
     fn test_parser() {
         let data = r#"
         [
         {
-            "url": "http://localhost:2461/endb"
-        },
-        {
-            "url": "https://google.com/"
-        },
-        {
-            "url": "http://osdfsdfksdf.comasdas"
+            "id": "test",
+            "services": [
+                {"url": "http://localhost:2461/endb"},
+                {"url": "https://google.com/", "method": "post"},
+                {"type": "tcp", "address": "localhost:5432", "interval": 5}
+            ]
         }
-        ]"#
-        .to_string();
+        ]"#;
 
-        let parsed = parse_config(data).unwrap();
+        let parsed = parse_config(data, ConfigFormat::Json).unwrap();
+        let services = &parsed[0].services;
 
-        assert!(parsed[0]["url"].as_str().unwrap() == "http://localhost:2461/endb");
-        assert!(parsed[1]["url"].as_str().unwrap() == "https://google.com/");
-        assert!(parsed[2]["url"].as_str().unwrap() == "http://osdfsdfksdf.comasdas");
+        assert_eq!(
+            services[0].url.as_deref(),
+            Some("http://localhost:2461/endb")
+        );
+        assert_eq!(services[0].interval, 10);
+        assert_eq!(services[1].method, Method::Post);
+        assert_eq!(services[2].service_type, ServiceType::Tcp);
+        assert_eq!(services[2].interval, 5);
     }
 
     #[test]
@@ -45,15 +650,134 @@ mod tests {
 ]
 }
     ]
-        "#
-        .to_string();
+        "#;
+
+        let parsed = parse_config(data, ConfigFormat::Json).unwrap();
+
+        assert!(parsed[0].id == "test");
+        assert!(parsed[0].services.len() == 2);
+        assert!(parsed[0].services[0].url.as_deref() == Some("http://localhost"));
+    }
+
+    #[test]
+    fn test_parser_formats() {
+        let yaml = r#"
+# the second service shares the settings of the first
+- id: web
+  services:
+    - &defaults
+      url: http://localhost:3000/check
+      interval: 5
+      call_timeout: 2
+    - <<: *defaults
+      url: http://localhost:3001/check
+      interval: 30
+"#;
+        let toml = r#"
+[[group]]
+id = "web"
 
-        let parsed = parse_config(data).unwrap();
+[[group.services]]
+url = "http://localhost:3000/check"
+interval = 5
+call_timeout = 2
 
-        assert!(parsed[0]["id"].as_str().unwrap() == "test");
-        assert!(parsed[0]["services"][0]["url"].as_str().unwrap() == "http://localhost");
+[[group.services]]
+url = "http://localhost:3001/check"
+interval = 30
+call_timeout = 2
+"#;
 
-        let services = parse_config(parsed[0]["services"].to_string()).unwrap();
-        assert!(services[0]["url"].as_str().unwrap() == "http://localhost");
+        let yaml = parse_config(yaml, ConfigFormat::Yaml).unwrap();
+        let toml = parse_config(toml, ConfigFormat::Toml).unwrap();
+        assert_eq!(yaml, toml);
+        assert_eq!(toml[0].services[0].interval, 5);
+        assert_eq!(toml[0].services[1].interval, 30);
+        assert_eq!(toml[0].services[1].call_timeout, 2);
+
+        let errors = parse_config("[[groups]]\nid = \"web\"", ConfigFormat::Toml).unwrap_err();
+        assert!(errors.errors()[0].contains("unknown field `groups`"));
+        assert_eq!(ConfigFormat::from_path("./config.yml"), ConfigFormat::Yaml);
+        assert_eq!(ConfigFormat::from_path("deploy.TOML"), ConfigFormat::Toml);
+        assert_eq!(ConfigFormat::from_path("./config.json"), ConfigFormat::Json);
+    }
+
+    #[test]
+    fn test_parser_errors() {
+        let data = r#"[
+            {"id": "a", "services": [{"url": "http://localhost", "stratgy": "json"}]},
+            {"services": [
+                {"url": "http://localhost", "interval": "often"},
+                {"url": "http://localhost", "conditions": [{"strategy": "fast"}]}
+            ]},
+            {"id": "b", "policy": {"quorum": 2, "typo": 1}, "services": [{"url": "http://localhost"}]},
+            {"id": "c", "policy": {"quorum": 2, "weighted": 0.5}, "services": [{"url": "http://localhost"}]}
+        ]"#;
+
+        let errors = parse_config(data, ConfigFormat::Json).unwrap_err();
+        let errors = errors.errors();
+
+        assert_eq!(errors.len(), 6, "{:?}", errors);
+        assert!(errors[0].starts_with("[0].services[0].stratgy: unknown field `stratgy`"));
+        assert!(errors[1].starts_with("[1]: missing field `id`"));
+        assert!(errors[2].starts_with("[1].services[0].interval: invalid type"));
+        assert!(
+            errors[3].starts_with("[1].services[1].conditions[0].strategy: unknown variant `fast`")
+        );
+        assert!(errors[4].starts_with("[2].policy.typo: unknown field `typo`"));
+        assert_eq!(
+            errors[5],
+            "[3].policy: expected exactly one of quorum or weighted"
+        );
+
+        assert!(parse_config("[{", ConfigFormat::Json).is_err());
+    }
+
+    #[test]
+    fn test_parser_validation() {
+        let data = r#"[
+            {"id": "web", "services": []},
+            {"id": "db", "services": [
+                {"type": "tcp", "address": "localhost:5432", "url": "http://localhost"},
+                {"type": "heartbeat", "grace": 30, "retries": 2},
+                {"url": "http://localhost", "grace": 30},
+                {"url": "http://localhost", "strategy": "regex", "conditions": [
+                    {"expected_status": 200, "conditions": [], "match": "any"}
+                ]},
+                {"url": "http://localhost", "match": "any"}
+            ]}
+        ]"#;
+
+        let errors = parse_config(data, ConfigFormat::Json).unwrap_err();
+        assert_eq!(
+            errors.errors(),
+            [
+                "[0].services: must not be empty",
+                "[1].services[0].url: not allowed when type=tcp",
+                "[1].services[1].retries: not allowed when type=heartbeat",
+                "[1].services[2].grace: not allowed when type=http",
+                "[1].services[3].strategy: not allowed together with conditions",
+                "[1].services[3].conditions[0].expected_status: not allowed together with conditions",
+                "[1].services[4].match: only allowed together with conditions",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parser_method() {
+        for (method, expected) in [("Post", Method::Post), ("gEt", Method::Get)] {
+            let data = format!(
+                r#"[{{"id":"test","services":[{{"url":"http://localhost","method":"{}"}}]}}]"#,
+                method
+            );
+            let parsed = parse_config(&data, ConfigFormat::Json).unwrap();
+            assert_eq!(parsed[0].services[0].method, expected);
+        }
+        let data = r#"[{"id":"test","services":[{"url":"http://localhost","method":"PUT"}]}]"#;
+        let errors = parse_config(data, ConfigFormat::Json).unwrap_err();
+        assert_eq!(
+            errors.errors(),
+            ["[0].services[0].method: invalid value: string \"PUT\", expected GET or POST"]
+        );
     }
 }
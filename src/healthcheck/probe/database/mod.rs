@@ -0,0 +1,108 @@
+pub mod mysql;
+pub mod postgres;
+
+use std::error::Error;
+
+/// How to log into a database, and what to ask it once logged in.
+#[derive(Debug, Clone, Default)]
+pub struct DatabaseLogin {
+    pub user: String,
+    pub password: Option<String>,
+    pub database: Option<String>,
+    /// Query to run after the handshake, e.g. `SELECT 1`.
+    pub query: Option<String>,
+    /// Text the first column of the query's first row has to be equal to.
+    pub expect: Option<String>,
+    /// Whether the server has to accept TLS. Postgres only.
+    pub tls: bool,
+    /// PEM file with extra root certificates to trust.
+    pub ca_file: Option<String>,
+}
+
+impl DatabaseLogin {
+    /// Compares the first value returned by the query with `expect`,
+    /// returning the mismatch if there's one.
+    fn compare(&self, value: Option<&str>) -> Option<String> {
+        let expect = self.expect.as_deref()?;
+        match value {
+            Some(value) if value == expect => None,
+            Some(value) => Some(format!("Query returned '{}', expected '{}'", value, expect)),
+            None => Some(format!("Query returned no value, expected '{}'", expect)),
+        }
+    }
+}
+
+/// Splits a `host:port` address, with the port defaulting to `default_port`.
+fn split_address(
+    address: &str,
+    default_port: u16,
+) -> Result<(String, u16), Box<dyn Error + Send + Sync>> {
+    let invalid = || format!("Invalid port in address: {}", address);
+    // only a bracketed IPv6 address carries a port, a bare one has colons of its own
+    if let Some(rest) = address.strip_prefix('[') {
+        let (host, port) = rest
+            .split_once(']')
+            .ok_or_else(|| format!("Unclosed bracket in address: {}", address))?;
+        return match port {
+            "" => Ok((host.to_string(), default_port)),
+            _ => match port.strip_prefix(':').map(str::parse) {
+                Some(Ok(port)) => Ok((host.to_string(), port)),
+                _ => Err(invalid().into()),
+            },
+        };
+    }
+    match address.split_once(':') {
+        Some((host, port)) if !port.contains(':') => {
+            Ok((host.to_string(), port.parse().map_err(|_| invalid())?))
+        }
+        _ => Ok((address.to_string(), default_port)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare() {
+        let login = DatabaseLogin {
+            expect: Some("f".to_string()),
+            ..DatabaseLogin::default()
+        };
+        assert_eq!(login.compare(Some("f")), None);
+        assert_eq!(
+            login.compare(Some("t")).unwrap(),
+            "Query returned 't', expected 'f'"
+        );
+        assert!(login.compare(None).is_some());
+        assert_eq!(DatabaseLogin::default().compare(None), None);
+    }
+
+    #[test]
+    fn test_split_address() {
+        assert_eq!(
+            split_address("db:6543", 5432).unwrap(),
+            ("db".to_string(), 6543)
+        );
+        assert_eq!(split_address("db", 5432).unwrap(), ("db".to_string(), 5432));
+        assert_eq!(
+            split_address("[::1]:3307", 3306).unwrap(),
+            ("::1".to_string(), 3307)
+        );
+        assert_eq!(
+            split_address("[::1]", 3306).unwrap(),
+            ("::1".to_string(), 3306)
+        );
+        assert_eq!(
+            split_address("2001:db8:0:0:0:0:0:5", 5432).unwrap(),
+            ("2001:db8:0:0:0:0:0:5".to_string(), 5432)
+        );
+        assert_eq!(
+            split_address("::1", 5432).unwrap(),
+            ("::1".to_string(), 5432)
+        );
+        assert!(split_address("db:port", 5432).is_err());
+        assert!(split_address("[::1]5432", 5432).is_err());
+        assert!(split_address("[::1:5432", 5432).is_err());
+    }
+}
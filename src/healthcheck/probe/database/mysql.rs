@@ -0,0 +1,305 @@
+use std::error::Error;
+use std::time::Duration;
+
+use mysql_async::prelude::Queryable;
+use mysql_async::{Conn, OptsBuilder, Row, Value};
+use tokio::time::timeout;
+
+use super::{split_address, DatabaseLogin};
+use crate::healthcheck::node::model::CheckResult;
+use crate::healthcheck::probe::TimedOut;
+
+/// Largest packet accepted from the server; a health check never needs more.
+const MAX_PACKET: usize = 1 << 20;
+
+/// Logs into the MySQL server at `address` and runs the login's query, all
+/// within `call_timeout`. Errors sent by the server, like a refused login,
+/// are reported as they are.
+pub async fn check(
+    address: &str,
+    login: &DatabaseLogin,
+    call_timeout: Duration,
+) -> Result<CheckResult, Box<dyn Error + Send + Sync>> {
+    let (host, port) = split_address(address, 3306)?;
+    let error = match timeout(call_timeout, session(host, port, login)).await {
+        Ok(Ok(error)) => error,
+        // errors the server sent are a verdict, the others a failed check
+        Ok(Err(mysql_async::Error::Server(err))) => {
+            Some(format!("ERROR {}: {}", err.code, err.message))
+        }
+        Ok(Err(err)) => return Err(err.into()),
+        Err(_) => return Err(TimedOut(call_timeout).into()),
+    };
+
+    Ok(CheckResult::from_error(error))
+}
+
+/// Runs the handshake and the query, returning the mismatch with the
+/// expected value, if any.
+async fn session(
+    host: String,
+    port: u16,
+    login: &DatabaseLogin,
+) -> Result<Option<String>, mysql_async::Error> {
+    let opts = OptsBuilder::default()
+        .ip_or_hostname(host)
+        .tcp_port(port)
+        .user(Some(&login.user))
+        .pass(login.password.as_ref())
+        .db_name(login.database.as_ref())
+        // with these set, the client doesn't query the server's settings
+        // or reconnect over its socket before the login's query
+        .prefer_socket(false)
+        .max_allowed_packet(Some(MAX_PACKET))
+        .wait_timeout(Some(0));
+    let mut connection = Conn::new(opts).await?;
+
+    let error = match &login.query {
+        Some(query) => {
+            let row: Option<Row> = connection.query_first(query).await?;
+            login.compare(row.and_then(first_value).as_deref())
+        }
+        None => None,
+    };
+    connection.disconnect().await?;
+
+    Ok(error)
+}
+
+/// The first column of `row` as text, `None` for SQL NULL.
+fn first_value(row: Row) -> Option<String> {
+    match row.as_ref(0)? {
+        Value::NULL => None,
+        Value::Bytes(bytes) => Some(String::from_utf8_lossy(bytes).to_string()),
+        value => Some(value.as_sql(false)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::healthcheck::node::model::NodeStatus;
+    use openssl::rsa::{Padding, Rsa};
+    use openssl::sha::{sha1, sha256};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    const NONCE: &[u8; 20] = b"abcdefghijklmnopqrst";
+
+    /// Packets of a server connection, numbered as the protocol wants.
+    struct Packets {
+        stream: TcpStream,
+        sequence: u8,
+    }
+
+    impl Packets {
+        async fn read(&mut self) -> Option<Vec<u8>> {
+            let mut header = [0u8; 4];
+            self.stream.read_exact(&mut header).await.ok()?;
+            let len = u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize;
+            self.sequence = header[3].wrapping_add(1);
+            let mut packet = vec![0u8; len];
+            self.stream.read_exact(&mut packet).await.ok()?;
+            Some(packet)
+        }
+
+        async fn write(&mut self, payload: &[u8]) {
+            let len = (payload.len() as u32).to_le_bytes();
+            let mut packet = vec![len[0], len[1], len[2], self.sequence];
+            packet.extend_from_slice(payload);
+            self.stream.write_all(&packet).await.unwrap();
+            self.sequence = self.sequence.wrapping_add(1);
+        }
+    }
+
+    /// The auth response the client sends for `plugin`.
+    fn scramble(plugin: &str, password: &str) -> Vec<u8> {
+        let (hash, salted) = match plugin {
+            // SHA1(password) XOR SHA1(nonce + SHA1(SHA1(password)))
+            "mysql_native_password" => {
+                let hash = sha1(password.as_bytes()).to_vec();
+                let salted = sha1(&[&NONCE[..], &sha1(&hash)].concat()).to_vec();
+                (hash, salted)
+            }
+            // SHA256(password) XOR SHA256(SHA256(SHA256(password)) + nonce)
+            _ => {
+                let hash = sha256(password.as_bytes()).to_vec();
+                let salted = sha256(&[&sha256(&hash)[..], NONCE].concat()).to_vec();
+                (hash, salted)
+            }
+        };
+        hash.iter().zip(salted).map(|(a, b)| a ^ b).collect()
+    }
+
+    /// A MySQL server using `plugin` that accepts `password` and answers
+    /// every query with a single value. With `full_auth`, a
+    /// caching_sha2_password server asks for the password encrypted with its
+    /// public key, as it does when its cache is empty.
+    async fn serve(
+        plugin: &'static str,
+        password: &'static str,
+        value: &'static str,
+        full_auth: bool,
+    ) -> String {
+        let key = Rsa::generate(1024).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let key = key.clone();
+                tokio::spawn(async move {
+                    let mut packets = Packets {
+                        stream,
+                        sequence: 0,
+                    };
+                    // protocol 41, secure connection and plugin auth
+                    let mut greeting = vec![10];
+                    greeting.extend_from_slice(b"8.0.36\0");
+                    greeting.extend_from_slice(&7u32.to_le_bytes());
+                    greeting.extend_from_slice(&NONCE[..8]);
+                    greeting.push(0);
+                    greeting.extend_from_slice(&[0x09, 0xa2, 45, 2, 0, 0x08, 0, 21]);
+                    greeting.extend_from_slice(&[0u8; 10]);
+                    greeting.extend_from_slice(&NONCE[8..]);
+                    greeting.push(0);
+                    greeting.extend_from_slice(plugin.as_bytes());
+                    greeting.push(0);
+                    packets.write(&greeting).await;
+
+                    let response = packets.read().await.unwrap();
+                    let user_end = 32 + response[32..].iter().position(|&b| b == 0).unwrap();
+                    let auth_len = response[user_end + 1] as usize;
+                    let auth = &response[user_end + 2..user_end + 2 + auth_len];
+                    let accepted = match full_auth {
+                        true => {
+                            packets.write(&[0x01, 4]).await;
+                            assert_eq!(packets.read().await.unwrap(), [0x02]);
+                            let mut pem = vec![0x01];
+                            pem.extend_from_slice(&key.public_key_to_pem().unwrap());
+                            packets.write(&pem).await;
+                            let encrypted = packets.read().await.unwrap();
+                            let mut plain = vec![0u8; key.size() as usize];
+                            let len = key
+                                .private_decrypt(&encrypted, &mut plain, Padding::PKCS1_OAEP)
+                                .unwrap();
+                            let plain: Vec<u8> = plain[..len]
+                                .iter()
+                                .zip(NONCE.iter().cycle())
+                                .map(|(byte, salt)| byte ^ salt)
+                                .collect();
+                            plain == [password.as_bytes(), &[0]].concat()
+                        }
+                        false => auth == scramble(plugin, password),
+                    };
+                    if !accepted {
+                        let mut error = vec![0xff, 0x15, 0x04, b'#'];
+                        error.extend_from_slice(b"28000Access denied for user 'monitor'");
+                        packets.write(&error).await;
+                        return;
+                    }
+                    if plugin == "caching_sha2_password" && !full_auth {
+                        packets.write(&[0x01, 3]).await;
+                    }
+                    packets.write(&[0, 0, 0, 2, 0, 0, 0]).await;
+
+                    while let Some(command) = packets.read().await {
+                        if command[0] != 0x03 {
+                            return;
+                        }
+                        let mut column = Vec::new();
+                        for part in ["def", "", "", "", "value", ""] {
+                            column.push(part.len() as u8);
+                            column.extend_from_slice(part.as_bytes());
+                        }
+                        column.extend_from_slice(&[0x0c, 45, 0, 1, 0, 0, 0, 0xfd, 0, 0, 0, 0, 0]);
+                        let mut row = vec![value.len() as u8];
+                        row.extend_from_slice(value.as_bytes());
+
+                        for packet in [
+                            &[1][..],
+                            &column,
+                            &[0xfe, 0, 0, 2, 0],
+                            &row,
+                            &[0xfe, 0, 0, 2, 0],
+                        ] {
+                            packets.write(packet).await;
+                        }
+                    }
+                });
+            }
+        });
+        addr
+    }
+
+    fn login(password: &str, expect: Option<&str>) -> DatabaseLogin {
+        DatabaseLogin {
+            user: "monitor".to_string(),
+            password: Some(password.to_string()),
+            database: Some("app".to_string()),
+            query: Some("SELECT 1".to_string()),
+            expect: expect.map(|expect| expect.to_string()),
+            ..DatabaseLogin::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_login_and_query() {
+        for plugin in ["mysql_native_password", "caching_sha2_password"] {
+            let addr = serve(plugin, "secret", "1", false).await;
+
+            let result = check(&addr, &login("secret", Some("1")), Duration::from_secs(5))
+                .await
+                .unwrap();
+            assert_eq!(result.status, NodeStatus::Healthy, "{}", plugin);
+
+            let result = check(&addr, &login("secret", Some("2")), Duration::from_secs(5))
+                .await
+                .unwrap();
+            assert_eq!(result.error.unwrap(), "Query returned '1', expected '2'");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refused_login() {
+        let addr = serve("mysql_native_password", "secret", "1", false).await;
+
+        let result = check(&addr, &login("wrong", None), Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(result.status, NodeStatus::Down);
+        assert_eq!(
+            result.error.unwrap(),
+            "ERROR 1045: Access denied for user 'monitor'"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_full_authentication() {
+        let addr = serve("caching_sha2_password", "secret", "1", true).await;
+
+        let result = check(&addr, &login("secret", Some("1")), Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(result.status, NodeStatus::Healthy);
+
+        let result = check(&addr, &login("wrong", None), Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(
+            result.error.unwrap(),
+            "ERROR 1045: Access denied for user 'monitor'"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unreachable() {
+        let result = check(
+            "127.0.0.1:1",
+            &login("secret", None),
+            Duration::from_secs(5),
+        )
+        .await;
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,376 @@
+use std::error::Error;
+use std::time::Duration;
+
+use openssl::x509::X509;
+use postgres_native_tls::MakeTlsConnector;
+use tokio::time::timeout;
+use tokio_postgres::config::SslMode;
+use tokio_postgres::{NoTls, SimpleQueryMessage};
+
+use super::{split_address, DatabaseLogin};
+use crate::healthcheck::node::model::CheckResult;
+use crate::healthcheck::probe::TimedOut;
+
+/// Logs into the Postgres server at `address` and runs the login's query,
+/// all within `call_timeout`. Errors sent by the server, like a refused
+/// login or a database still starting up, are reported as they are.
+///
+/// With `login.tls`, the server has to accept TLS and present a certificate
+/// valid for the address's host.
+pub async fn check(
+    address: &str,
+    login: &DatabaseLogin,
+    call_timeout: Duration,
+) -> Result<CheckResult, Box<dyn Error + Send + Sync>> {
+    let tls = match login.tls {
+        true => Some(connector(login.ca_file.as_deref()).await?),
+        false => None,
+    };
+    let error = match timeout(call_timeout, session(address, login, tls, call_timeout)).await {
+        Ok(Ok(error)) => error,
+        // errors the server sent are a verdict, the others a failed check
+        Ok(Err(err)) if err.as_db_error().is_some() => Some(describe(&err)),
+        Ok(Err(err)) => return Err(describe(&err).into()),
+        Err(_) => return Err(TimedOut(call_timeout).into()),
+    };
+
+    Ok(CheckResult::from_error(error))
+}
+
+/// Trusts the system's root certificates and the ones in `ca_file`.
+async fn connector(
+    ca_file: Option<&str>,
+) -> Result<MakeTlsConnector, Box<dyn Error + Send + Sync>> {
+    let mut builder = native_tls::TlsConnector::builder();
+    if let Some(ca_file) = ca_file {
+        for cert in X509::stack_from_pem(&tokio::fs::read(ca_file).await?)? {
+            builder.add_root_certificate(native_tls::Certificate::from_der(&cert.to_der()?)?);
+        }
+    }
+    Ok(MakeTlsConnector::new(builder.build()?))
+}
+
+async fn session(
+    address: &str,
+    login: &DatabaseLogin,
+    tls: Option<MakeTlsConnector>,
+    call_timeout: Duration,
+) -> Result<Option<String>, tokio_postgres::Error> {
+    let (host, port) = match split_address(address, 5432) {
+        Ok(host_port) => host_port,
+        Err(err) => return Ok(Some(err.to_string())),
+    };
+
+    let mut config = tokio_postgres::Config::new();
+    config
+        .host(host)
+        .port(port)
+        .user(&login.user)
+        .application_name("healthcheckify")
+        .connect_timeout(call_timeout);
+    if let Some(password) = &login.password {
+        config.password(password);
+    }
+    if let Some(database) = &login.database {
+        config.dbname(database);
+    }
+
+    // the connection ends once the client is dropped
+    let client = match tls {
+        Some(tls) => {
+            config.ssl_mode(SslMode::Require);
+            let (client, connection) = config.connect(tls).await?;
+            tokio::spawn(connection);
+            client
+        }
+        None => {
+            let (client, connection) = config.connect(NoTls).await?;
+            tokio::spawn(connection);
+            client
+        }
+    };
+
+    let query = match &login.query {
+        Some(query) => query,
+        None => return Ok(None),
+    };
+    let value = client
+        .simple_query(query)
+        .await?
+        .into_iter()
+        .find_map(|message| match message {
+            SimpleQueryMessage::Row(row) => Some(row.get(0).map(|value| value.to_string())),
+            _ => None,
+        })
+        .flatten();
+
+    Ok(login.compare(value.as_deref()))
+}
+
+/// The server's own message for errors it sent, the client error otherwise.
+fn describe(err: &tokio_postgres::Error) -> String {
+    match (err.as_db_error(), err.source()) {
+        (Some(db_error), _) => format!("{}: {}", db_error.severity(), db_error.message()),
+        (None, Some(source)) => format!("{}: {}", err, source),
+        (None, None) => err.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::healthcheck::node::model::NodeStatus;
+    use openssl::asn1::Asn1Time;
+    use openssl::hash::MessageDigest;
+    use openssl::pkey::PKey;
+    use openssl::rsa::Rsa;
+    use openssl::ssl::{Ssl, SslAcceptor, SslMethod};
+    use openssl::x509::extension::SubjectAlternativeName;
+    use openssl::x509::X509NameBuilder;
+    use std::path::PathBuf;
+    use std::pin::Pin;
+    use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio_openssl::SslStream;
+
+    /// Code of the startup message asking for TLS.
+    const SSL_REQUEST: i32 = 80877103;
+
+    /// Appends a backend message with its tag and length.
+    fn message(out: &mut Vec<u8>, tag: u8, body: &[u8]) {
+        out.push(tag);
+        out.extend_from_slice(&(body.len() as i32 + 4).to_be_bytes());
+        out.extend_from_slice(body);
+    }
+
+    async fn read_message(stream: &mut (impl AsyncRead + Unpin)) -> (u8, Vec<u8>) {
+        let tag = stream.read_u8().await.unwrap();
+        let len = stream.read_i32().await.unwrap() as usize;
+        let mut body = vec![0u8; len - 4];
+        stream.read_exact(&mut body).await.unwrap();
+        (tag, body)
+    }
+
+    /// A Postgres server asking for `password` in clear text and answering
+    /// every query with a single text value. TLS is only accepted when an
+    /// `acceptor` is given.
+    async fn serve(
+        password: &'static str,
+        value: &'static str,
+        acceptor: Option<SslAcceptor>,
+    ) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    // startup message, untagged
+                    let len = stream.read_i32().await.unwrap() as usize;
+                    let mut startup = vec![0u8; len - 4];
+                    stream.read_exact(&mut startup).await.unwrap();
+                    if startup[..4] != SSL_REQUEST.to_be_bytes() {
+                        return session(stream, password, value).await;
+                    }
+                    match acceptor {
+                        Some(acceptor) => {
+                            stream.write_all(b"S").await.unwrap();
+                            let ssl = Ssl::new(acceptor.context()).unwrap();
+                            let mut stream = SslStream::new(ssl, stream).unwrap();
+                            if Pin::new(&mut stream).accept().await.is_err() {
+                                return;
+                            }
+                            skip_startup(&mut stream).await;
+                            session(stream, password, value).await
+                        }
+                        None => {
+                            stream.write_all(b"N").await.unwrap();
+                            skip_startup(&mut stream).await;
+                            session(stream, password, value).await
+                        }
+                    }
+                });
+            }
+        });
+        addr
+    }
+
+    async fn skip_startup(stream: &mut (impl AsyncRead + Unpin)) {
+        let Ok(len) = stream.read_i32().await else {
+            return;
+        };
+        let mut startup = vec![0u8; len as usize - 4];
+        _ = stream.read_exact(&mut startup).await;
+    }
+
+    async fn session(mut stream: impl AsyncRead + AsyncWrite + Unpin, password: &str, value: &str) {
+        let mut out = Vec::new();
+        message(&mut out, b'R', &3i32.to_be_bytes());
+        if stream.write_all(&out).await.is_err() {
+            return;
+        }
+
+        let (_, body) = read_message(&mut stream).await;
+        let mut out = Vec::new();
+        if body != [password.as_bytes(), b"\0"].concat() {
+            message(
+                &mut out,
+                b'E',
+                b"SFATAL\0VFATAL\0C28P01\0Mpassword authentication failed\0\0",
+            );
+            stream.write_all(&out).await.unwrap();
+            return;
+        }
+        message(&mut out, b'R', &0i32.to_be_bytes());
+        message(&mut out, b'Z', b"I");
+        stream.write_all(&out).await.unwrap();
+
+        loop {
+            let (tag, _) = read_message(&mut stream).await;
+            if tag != b'Q' {
+                return;
+            }
+            let mut row_description = 1i16.to_be_bytes().to_vec();
+            row_description.extend_from_slice(b"value\0");
+            row_description.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+            row_description.extend_from_slice(&25i32.to_be_bytes());
+            row_description.extend_from_slice(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+            row_description.extend_from_slice(&[0, 0]);
+            let mut data_row = 1i16.to_be_bytes().to_vec();
+            data_row.extend_from_slice(&(value.len() as i32).to_be_bytes());
+            data_row.extend_from_slice(value.as_bytes());
+
+            let mut out = Vec::new();
+            message(&mut out, b'T', &row_description);
+            message(&mut out, b'D', &data_row);
+            message(&mut out, b'C', b"SELECT 1\0");
+            message(&mut out, b'Z', b"I");
+            stream.write_all(&out).await.unwrap();
+        }
+    }
+
+    /// A certificate written to a temporary file, removed once dropped.
+    struct CaFile(PathBuf);
+
+    impl Drop for CaFile {
+        fn drop(&mut self) {
+            _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    /// An acceptor with a self-signed certificate for 127.0.0.1, and that
+    /// certificate's file.
+    fn acceptor(name: &str) -> (SslAcceptor, CaFile) {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let mut subject = X509NameBuilder::new().unwrap();
+        subject.append_entry_by_text("CN", "127.0.0.1").unwrap();
+        let subject = subject.build();
+
+        let mut cert = X509::builder().unwrap();
+        cert.set_version(2).unwrap();
+        cert.set_subject_name(&subject).unwrap();
+        cert.set_issuer_name(&subject).unwrap();
+        cert.set_pubkey(&key).unwrap();
+        cert.set_not_before(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        cert.set_not_after(&Asn1Time::days_from_now(30).unwrap())
+            .unwrap();
+        let san = SubjectAlternativeName::new()
+            .ip("127.0.0.1")
+            .build(&cert.x509v3_context(None, None))
+            .unwrap();
+        cert.append_extension(san).unwrap();
+        cert.sign(&key, MessageDigest::sha256()).unwrap();
+        let cert = cert.build();
+
+        let ca_file = std::env::temp_dir().join(format!("healthcheckify-postgres-{}.pem", name));
+        std::fs::write(&ca_file, cert.to_pem().unwrap()).unwrap();
+
+        let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+        acceptor.set_private_key(&key).unwrap();
+        acceptor.set_certificate(&cert).unwrap();
+        (acceptor.build(), CaFile(ca_file))
+    }
+
+    fn login(password: &str, expect: Option<&str>) -> DatabaseLogin {
+        DatabaseLogin {
+            user: "monitor".to_string(),
+            password: Some(password.to_string()),
+            database: Some("app".to_string()),
+            query: Some("SELECT pg_is_in_recovery()".to_string()),
+            expect: expect.map(|expect| expect.to_string()),
+            ..DatabaseLogin::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_login_and_query() {
+        let addr = serve("secret", "f", None).await;
+
+        let result = check(&addr, &login("secret", Some("f")), Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(result.status, NodeStatus::Healthy);
+
+        let result = check(&addr, &login("secret", Some("t")), Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(result.error.unwrap(), "Query returned 'f', expected 't'");
+    }
+
+    #[tokio::test]
+    async fn test_refused_login() {
+        let addr = serve("secret", "f", None).await;
+
+        let result = check(&addr, &login("wrong", None), Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(result.status, NodeStatus::Down);
+        assert_eq!(
+            result.error.unwrap(),
+            "FATAL: password authentication failed"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unreachable() {
+        let result = check(
+            "127.0.0.1:1",
+            &login("secret", None),
+            Duration::from_secs(5),
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tls() {
+        let (acceptor, ca_file) = acceptor("tls");
+        let addr = serve("secret", "f", Some(acceptor)).await;
+        let login = DatabaseLogin {
+            tls: true,
+            ca_file: Some(ca_file.0.to_string_lossy().to_string()),
+            ..login("secret", Some("f"))
+        };
+
+        let result = check(&addr, &login, Duration::from_secs(5)).await.unwrap();
+        assert_eq!(result.status, NodeStatus::Healthy);
+
+        // the certificate isn't trusted without the CA file
+        let untrusted = DatabaseLogin {
+            ca_file: None,
+            ..login.clone()
+        };
+        assert!(check(&addr, &untrusted, Duration::from_secs(5))
+            .await
+            .is_err());
+
+        // a server without TLS is refused
+        let addr = serve("secret", "f", None).await;
+        let err = check(&addr, &login, Duration::from_secs(5))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("TLS"), "{}", err);
+    }
+}
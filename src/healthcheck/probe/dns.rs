@@ -0,0 +1,428 @@
+use std::error::Error;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+use tokio::net::UdpSocket;
+use tokio::time::timeout;
+
+use crate::healthcheck::node::model::CheckResult;
+use crate::healthcheck::probe::TimedOut;
+
+/// Record types the DNS probe can query.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecordType {
+    A,
+    AAAA,
+    CNAME,
+    TXT,
+    SRV,
+}
+
+impl RecordType {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.to_ascii_uppercase().as_str() {
+            "A" => Ok(RecordType::A),
+            "AAAA" => Ok(RecordType::AAAA),
+            "CNAME" => Ok(RecordType::CNAME),
+            "TXT" => Ok(RecordType::TXT),
+            "SRV" => Ok(RecordType::SRV),
+            _ => Err(format!("Unsupported DNS record type: {}", name)),
+        }
+    }
+
+    fn code(&self) -> u16 {
+        match self {
+            RecordType::A => 1,
+            RecordType::CNAME => 5,
+            RecordType::TXT => 16,
+            RecordType::AAAA => 28,
+            RecordType::SRV => 33,
+        }
+    }
+}
+
+/// A name to resolve and what the answer has to look like.
+#[derive(Debug, Clone)]
+pub struct DnsQuery {
+    pub name: String,
+    pub record_type: RecordType,
+    pub resolver: SocketAddr,
+    /// Records that all have to be in the answer, written the way they are
+    /// reported: `10.0.0.1`, `alias.example.com`, `10 5 5432 db.example.com`.
+    pub expected: Vec<String>,
+    /// The node is down when the resolver takes longer to answer.
+    pub max_latency: Option<Duration>,
+}
+
+/// Resolves the query against its resolver and checks the records and the
+/// response time.
+pub async fn check(
+    query: &DnsQuery,
+    call_timeout: Duration,
+) -> Result<CheckResult, Box<dyn Error + Send + Sync>> {
+    let started = Instant::now();
+    let records = match timeout(call_timeout, resolve(query)).await {
+        Ok(records) => records?,
+        Err(_) => return Err(TimedOut(call_timeout).into()),
+    };
+    let latency = started.elapsed();
+
+    let missing: Vec<&String> = query
+        .expected
+        .iter()
+        .filter(|expected| {
+            !records
+                .iter()
+                .any(|record| record.eq_ignore_ascii_case(expected))
+        })
+        .collect();
+
+    let error = if records.is_empty() {
+        Some(format!(
+            "No {:?} records for {}",
+            query.record_type, query.name
+        ))
+    } else if !missing.is_empty() {
+        Some(format!("Missing records {:?}, got {:?}", missing, records))
+    } else {
+        match query.max_latency {
+            Some(max) if latency > max => Some(format!(
+                "Resolver took {}ms, more than {}ms",
+                latency.as_millis(),
+                max.as_millis()
+            )),
+            _ => None,
+        }
+    };
+
+    Ok(CheckResult::from_error(error))
+}
+
+/// Sends a single recursive query over UDP and returns the records of the
+/// requested type found in the answer section.
+async fn resolve(query: &DnsQuery) -> Result<Vec<String>, Box<dyn Error + Send + Sync>> {
+    let bind: SocketAddr = match query.resolver {
+        SocketAddr::V4(_) => "0.0.0.0:0".parse()?,
+        SocketAddr::V6(_) => "[::]:0".parse()?,
+    };
+    let socket = UdpSocket::bind(bind).await?;
+    socket.connect(query.resolver).await?;
+
+    let id: u16 = rand::thread_rng().gen();
+    socket
+        .send(&encode_query(id, &query.name, query.record_type)?)
+        .await?;
+
+    let mut buf = [0u8; 4096];
+    loop {
+        let len = socket.recv(&mut buf).await?;
+        let response = &buf[..len];
+        // ignore stray datagrams that don't answer our query
+        if len >= 2 && u16::from_be_bytes([response[0], response[1]]) == id {
+            return decode_response(response, query.record_type);
+        }
+    }
+}
+
+fn encode_query(
+    id: u16,
+    name: &str,
+    record_type: RecordType,
+) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    let mut packet = Vec::with_capacity(32 + name.len());
+    packet.extend_from_slice(&id.to_be_bytes());
+    // recursion desired, one question
+    packet.extend_from_slice(&[0x01, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0]);
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(format!("Invalid DNS name: {}", name).into());
+        }
+        packet.push(label.len() as u8);
+        packet.extend_from_slice(label.as_bytes());
+    }
+    packet.push(0);
+    packet.extend_from_slice(&record_type.code().to_be_bytes());
+    // class IN
+    packet.extend_from_slice(&1u16.to_be_bytes());
+
+    Ok(packet)
+}
+
+fn decode_response(
+    packet: &[u8],
+    record_type: RecordType,
+) -> Result<Vec<String>, Box<dyn Error + Send + Sync>> {
+    let truncated = || "Truncated DNS response";
+    if packet.len() < 12 {
+        return Err(truncated().into());
+    }
+    // answers too large for a datagram are cut short and flagged TC
+    if packet[2] & 0x02 != 0 {
+        return Err("Answer truncated by the resolver (TC), too large for UDP".into());
+    }
+    let rcode = packet[3] & 0x0f;
+    match rcode {
+        0 => {}
+        2 => return Err("Resolver failed (SERVFAIL)".into()),
+        3 => return Err("Name does not exist (NXDOMAIN)".into()),
+        5 => return Err("Query refused (REFUSED)".into()),
+        _ => return Err(format!("Resolver answered with error code {}", rcode).into()),
+    }
+    let questions = u16::from_be_bytes([packet[4], packet[5]]);
+    let answers = u16::from_be_bytes([packet[6], packet[7]]);
+
+    let mut offset = 12;
+    for _ in 0..questions {
+        offset = skip_name(packet, offset).ok_or_else(truncated)? + 4;
+    }
+
+    let mut records = Vec::with_capacity(answers as usize);
+    for _ in 0..answers {
+        offset = skip_name(packet, offset).ok_or_else(truncated)?;
+        let header = packet.get(offset..offset + 10).ok_or_else(truncated)?;
+        let kind = u16::from_be_bytes([header[0], header[1]]);
+        let length = u16::from_be_bytes([header[8], header[9]]) as usize;
+        let start = offset + 10;
+        let data = packet.get(start..start + length).ok_or_else(truncated)?;
+        offset = start + length;
+
+        if kind != record_type.code() {
+            continue;
+        }
+        let record = match record_type {
+            RecordType::A if length == 4 => {
+                Ipv4Addr::new(data[0], data[1], data[2], data[3]).to_string()
+            }
+            RecordType::AAAA if length == 16 => {
+                let mut octets = [0u8; 16];
+                octets.copy_from_slice(data);
+                Ipv6Addr::from(octets).to_string()
+            }
+            RecordType::CNAME => read_name(packet, start).ok_or_else(truncated)?,
+            RecordType::TXT => {
+                let mut text = String::new();
+                let mut at = 0;
+                while at < data.len() {
+                    let len = data[at] as usize;
+                    let chunk = data.get(at + 1..at + 1 + len).ok_or_else(truncated)?;
+                    text += &String::from_utf8_lossy(chunk);
+                    at += 1 + len;
+                }
+                text
+            }
+            RecordType::SRV if length > 6 => {
+                let field = |at: usize| u16::from_be_bytes([data[at], data[at + 1]]);
+                let target = read_name(packet, start + 6).ok_or_else(truncated)?;
+                format!("{} {} {} {}", field(0), field(2), field(4), target)
+            }
+            _ => return Err(truncated().into()),
+        };
+        records.push(record);
+    }
+
+    Ok(records)
+}
+
+/// Returns the offset right after the name starting at `offset`.
+fn skip_name(packet: &[u8], mut offset: usize) -> Option<usize> {
+    loop {
+        let len = *packet.get(offset)? as usize;
+        if len == 0 {
+            return Some(offset + 1);
+        }
+        if len & 0xc0 == 0xc0 {
+            return Some(offset + 2);
+        }
+        offset += 1 + len;
+    }
+}
+
+/// Reads a possibly compressed name, without the trailing dot.
+fn read_name(packet: &[u8], mut offset: usize) -> Option<String> {
+    let mut labels = Vec::new();
+    // bound the number of pointers followed, to survive pointer loops
+    for _ in 0..128 {
+        let len = *packet.get(offset)? as usize;
+        if len == 0 {
+            return Some(labels.join("."));
+        }
+        if len & 0xc0 == 0xc0 {
+            offset = ((len & 0x3f) << 8) | *packet.get(offset + 1)? as usize;
+            continue;
+        }
+        let label = packet.get(offset + 1..offset + 1 + len)?;
+        labels.push(String::from_utf8_lossy(label).to_string());
+        offset += 1 + len;
+    }
+    None
+}
+
+/// The first `nameserver` of `/etc/resolv.conf`, for queries without an
+/// explicit resolver.
+pub fn system_resolver() -> Option<SocketAddr> {
+    let conf = std::fs::read_to_string("/etc/resolv.conf").ok()?;
+    conf.lines().find_map(|line| {
+        let address = line.trim().strip_prefix("nameserver")?.trim();
+        let ip: std::net::IpAddr = address.parse().ok()?;
+        Some(SocketAddr::new(ip, 53))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::healthcheck::node::model::NodeStatus;
+
+    /// Starts a DNS server answering every query with the given records of
+    /// type `kind`, each as raw record data. Names in the data may point back
+    /// to the question name at offset 12.
+    async fn serve(kind: u16, rcode: u8, records: Vec<Vec<u8>>) -> SocketAddr {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 512];
+            loop {
+                let (len, peer) = socket.recv_from(&mut buf).await.unwrap();
+                let question_end = skip_name(&buf, 12).unwrap() + 4;
+                let mut response = buf[..question_end].to_vec();
+                response[2] = 0x81;
+                response[3] = 0x80 | rcode;
+                response[6..8].copy_from_slice(&(records.len() as u16).to_be_bytes());
+                for data in &records {
+                    // name pointer to the question, type, class IN, ttl 60
+                    response.extend_from_slice(&[0xc0, 12]);
+                    response.extend_from_slice(&kind.to_be_bytes());
+                    response.extend_from_slice(&[0, 1, 0, 0, 0, 60]);
+                    response.extend_from_slice(&(data.len() as u16).to_be_bytes());
+                    response.extend_from_slice(data);
+                }
+                assert!(len > 12);
+                socket.send_to(&response, peer).await.unwrap();
+            }
+        });
+        addr
+    }
+
+    fn query(resolver: SocketAddr, record_type: RecordType, expected: &[&str]) -> DnsQuery {
+        DnsQuery {
+            name: "service.internal".to_string(),
+            record_type,
+            resolver,
+            expected: expected.iter().map(|e| e.to_string()).collect(),
+            max_latency: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_a_records() {
+        let resolver = serve(1, 0, vec![vec![10, 0, 0, 1], vec![10, 0, 0, 2]]).await;
+
+        let result = check(
+            &query(resolver, RecordType::A, &["10.0.0.2"]),
+            Duration::from_secs(1),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.status, NodeStatus::Healthy);
+
+        let result = check(
+            &query(resolver, RecordType::A, &["10.0.0.3"]),
+            Duration::from_secs(1),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.status, NodeStatus::Down);
+        assert!(result.error.unwrap().contains("10.0.0.3"));
+    }
+
+    #[tokio::test]
+    async fn test_cname_txt_srv_records() {
+        // "alias" followed by a pointer to the question name
+        let cname = serve(5, 0, vec![b"\x05alias\xc0\x0c".to_vec()]).await;
+        let result = check(
+            &query(cname, RecordType::CNAME, &["alias.service.internal"]),
+            Duration::from_secs(1),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.status, NodeStatus::Healthy);
+
+        let txt = serve(16, 0, vec![b"\x05hello\x06 world".to_vec()]).await;
+        let result = check(
+            &query(txt, RecordType::TXT, &["hello world"]),
+            Duration::from_secs(1),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.status, NodeStatus::Healthy);
+
+        let srv = serve(
+            33,
+            0,
+            vec![b"\x00\x0a\x00\x05\x15\x38\x02db\xc0\x0c".to_vec()],
+        )
+        .await;
+        let result = check(
+            &query(srv, RecordType::SRV, &["10 5 5432 db.service.internal"]),
+            Duration::from_secs(1),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.status, NodeStatus::Healthy);
+    }
+
+    #[tokio::test]
+    async fn test_failures() {
+        let nxdomain = serve(1, 3, vec![]).await;
+        let result = check(&query(nxdomain, RecordType::A, &[]), Duration::from_secs(1)).await;
+        assert!(result.unwrap_err().to_string().contains("NXDOMAIN"));
+
+        let empty = serve(1, 0, vec![]).await;
+        let result = check(&query(empty, RecordType::AAAA, &[]), Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert_eq!(result.status, NodeStatus::Down);
+
+        let mut slow = query(
+            serve(1, 0, vec![vec![10, 0, 0, 1]]).await,
+            RecordType::A,
+            &[],
+        );
+        slow.max_latency = Some(Duration::ZERO);
+        let result = check(&slow, Duration::from_secs(1)).await.unwrap();
+        assert!(result.error.unwrap().starts_with("Resolver took"));
+    }
+
+    #[tokio::test]
+    async fn test_timeout() {
+        // nothing answers on this socket
+        let silent = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let resolver = silent.local_addr().unwrap();
+        let result = check(
+            &query(resolver, RecordType::A, &[]),
+            Duration::from_millis(200),
+        )
+        .await;
+        assert!(result.unwrap_err().to_string().starts_with("Timed out"));
+    }
+
+    #[test]
+    fn test_truncated_answer() {
+        let mut packet = encode_query(7, "a.bc", RecordType::A).unwrap();
+        packet[2] = 0x83;
+        packet[3] = 0x80;
+        let error = decode_response(&packet, RecordType::A).unwrap_err();
+        assert!(error.to_string().contains("(TC)"));
+    }
+
+    #[test]
+    fn test_encode_query() {
+        let packet = encode_query(7, "a.bc", RecordType::SRV).unwrap();
+        assert_eq!(
+            packet,
+            [0, 7, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0, 1, b'a', 2, b'b', b'c', 0, 0, 33, 0, 1]
+        );
+        assert!(encode_query(7, "a..b", RecordType::A).is_err());
+    }
+}
@@ -0,0 +1,164 @@
+use std::error::Error;
+use std::process::Stdio;
+use std::time::Duration;
+
+use tokio::io::AsyncReadExt;
+use tokio::process::Command;
+use tokio::time::timeout;
+
+use crate::healthcheck::node::model::{CheckResult, NodeStatus};
+use crate::healthcheck::probe::TimedOut;
+
+/// Most bytes of output kept from a command; the rest is read and dropped.
+const MAX_OUTPUT: usize = 4096;
+
+/// Lines of output kept as the status message.
+const MAX_LINES: usize = 5;
+
+/// A local command whose exit code tells the status of a node.
+#[derive(Debug, Clone, Default)]
+pub struct ExecCommand {
+    pub program: String,
+    pub args: Vec<String>,
+    /// Extra environment variables, on top of healthcheckify's own.
+    pub env: Vec<(String, String)>,
+}
+
+impl ExecCommand {
+    /// The command line, as shown in place of a url.
+    pub fn display(&self) -> String {
+        let mut line = self.program.clone();
+        for arg in self.args.iter() {
+            line.push(' ');
+            line.push_str(arg);
+        }
+        line
+    }
+}
+
+/// Runs the command, killing it if it's still running after
+/// `call_timeout`. Exit codes follow the Nagios plugin convention: 0 is
+/// healthy, 1 degraded, and 2 or anything else down. The first lines of
+/// what it prints are kept as the result's `output`.
+pub async fn check(
+    command: &ExecCommand,
+    call_timeout: Duration,
+) -> Result<CheckResult, Box<dyn Error + Send + Sync>> {
+    let mut child = Command::new(&command.program)
+        .args(&command.args)
+        .envs(command.env.iter().map(|(key, value)| (key, value)))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()?;
+    let mut stdout = child.stdout.take().ok_or("Couldn't capture the output")?;
+
+    let run = async {
+        let mut output = Vec::with_capacity(MAX_OUTPUT);
+        (&mut stdout)
+            .take(MAX_OUTPUT as u64)
+            .read_to_end(&mut output)
+            .await?;
+        // keep the pipe drained so the command doesn't block on a full one
+        tokio::io::copy(&mut stdout, &mut tokio::io::sink()).await?;
+        let status = child.wait().await?;
+        Ok::<_, std::io::Error>((status, output))
+    };
+    let (status, output) = match timeout(call_timeout, run).await {
+        Ok(result) => result?,
+        Err(_) => {
+            child.kill().await?;
+            return Err(TimedOut(call_timeout).into());
+        }
+    };
+
+    let result = match status.code() {
+        Some(0) => CheckResult::new(NodeStatus::Healthy, None),
+        Some(1) => CheckResult::new(NodeStatus::Degraded, Some("Exited with code 1".to_string())),
+        Some(code) => CheckResult::failed(format!("Exited with code {}", code)),
+        None => CheckResult::failed("Terminated by a signal".to_string()),
+    };
+
+    Ok(CheckResult {
+        output: first_lines(&output),
+        ..result
+    })
+}
+
+fn first_lines(output: &[u8]) -> Option<String> {
+    let output = String::from_utf8_lossy(output);
+    let lines: Vec<&str> = output
+        .lines()
+        .map(|line| line.trim_end())
+        .filter(|line| !line.is_empty())
+        .take(MAX_LINES)
+        .collect();
+    if lines.is_empty() {
+        None
+    } else {
+        Some(lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sh(script: &str) -> ExecCommand {
+        ExecCommand {
+            program: "sh".to_string(),
+            args: vec!["-c".to_string(), script.to_string()],
+            env: vec![("CHECK_NAME".to_string(), "disk".to_string())],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_exit_codes() {
+        let timeout = Duration::from_secs(5);
+
+        let result = check(&sh("echo \"$CHECK_NAME OK\"; exit 0"), timeout)
+            .await
+            .unwrap();
+        assert_eq!(result.status, NodeStatus::Healthy);
+        assert_eq!(result.output.as_deref(), Some("disk OK"));
+
+        let result = check(&sh("echo 'WARNING - 85% used'; exit 1"), timeout)
+            .await
+            .unwrap();
+        assert_eq!(result.status, NodeStatus::Degraded);
+        assert_eq!(result.output.as_deref(), Some("WARNING - 85% used"));
+
+        let result = check(&sh("exit 2"), timeout).await.unwrap();
+        assert_eq!(result.status, NodeStatus::Down);
+        assert_eq!(result.error.as_deref(), Some("Exited with code 2"));
+        assert_eq!(result.output, None);
+
+        let result = check(&sh("exit 3"), timeout).await.unwrap();
+        assert_eq!(result.status, NodeStatus::Down);
+    }
+
+    #[tokio::test]
+    async fn test_bounded_output() {
+        let result = check(&sh("seq 1 100000"), Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(result.status, NodeStatus::Healthy);
+        assert_eq!(result.output.as_deref(), Some("1\n2\n3\n4\n5"));
+    }
+
+    #[tokio::test]
+    async fn test_timeout() {
+        let result = check(&sh("sleep 10"), Duration::from_millis(200)).await;
+        assert!(result.unwrap_err().to_string().starts_with("Timed out"));
+    }
+
+    #[tokio::test]
+    async fn test_missing_program() {
+        let command = ExecCommand {
+            program: "/nonexistent/check".to_string(),
+            ..ExecCommand::default()
+        };
+        assert!(check(&command, Duration::from_secs(1)).await.is_err());
+    }
+}
@@ -0,0 +1,159 @@
+use std::error::Error;
+use std::time::Duration;
+
+use tokio::time::timeout;
+use tonic::transport::{Certificate, ClientTlsConfig, Endpoint};
+use tonic_health::pb::health_check_response::ServingStatus;
+use tonic_health::pb::health_client::HealthClient;
+use tonic_health::pb::HealthCheckRequest;
+
+use crate::healthcheck::node::model::{CheckResult, NodeStatus};
+use crate::healthcheck::probe::TimedOut;
+
+/// Which service to ask about over the `grpc.health.v1.Health` protocol,
+/// and how to reach it.
+#[derive(Debug, Clone, Default)]
+pub struct GrpcTarget {
+    /// Service name sent in the request; empty asks about the server as a
+    /// whole.
+    pub service: String,
+    pub tls: bool,
+    /// Name the certificate has to be valid for, when it isn't the host.
+    pub server_name: Option<String>,
+    /// PEM file with extra root certificates to trust.
+    pub ca_file: Option<String>,
+}
+
+/// Calls `grpc.health.v1.Health/Check` on `address` (`host:port`), all
+/// within `call_timeout`. `SERVING` is healthy, `UNKNOWN` degraded and
+/// anything else down.
+pub async fn check(
+    address: &str,
+    target: &GrpcTarget,
+    call_timeout: Duration,
+) -> Result<CheckResult, Box<dyn Error + Send + Sync>> {
+    match timeout(call_timeout, health_check(address, target, call_timeout)).await {
+        Ok(result) => result,
+        Err(_) => Err(TimedOut(call_timeout).into()),
+    }
+}
+
+async fn health_check(
+    address: &str,
+    target: &GrpcTarget,
+    call_timeout: Duration,
+) -> Result<CheckResult, Box<dyn Error + Send + Sync>> {
+    let scheme = if target.tls { "https" } else { "http" };
+    let mut endpoint = Endpoint::from_shared(format!("{}://{}", scheme, address))?
+        .connect_timeout(call_timeout)
+        .timeout(call_timeout);
+    if target.tls {
+        let mut tls = ClientTlsConfig::new().with_native_roots();
+        if let Some(server_name) = &target.server_name {
+            tls = tls.domain_name(server_name);
+        }
+        if let Some(ca_file) = &target.ca_file {
+            tls = tls.ca_certificate(Certificate::from_pem(tokio::fs::read(ca_file).await?));
+        }
+        endpoint = endpoint.tls_config(tls)?;
+    }
+
+    let mut client = HealthClient::new(endpoint.connect().await?);
+    let request = HealthCheckRequest {
+        service: target.service.clone(),
+    };
+    let status = match client.check(request).await {
+        Ok(response) => response.into_inner().status(),
+        Err(status) => {
+            return Ok(CheckResult::failed(format!(
+                "Health check failed: {:?}: {}",
+                status.code(),
+                status.message()
+            )))
+        }
+    };
+
+    Ok(match status {
+        ServingStatus::Serving => CheckResult::new(NodeStatus::Healthy, None),
+        ServingStatus::Unknown => CheckResult::new(
+            NodeStatus::Degraded,
+            Some("Service status is UNKNOWN".to_string()),
+        ),
+        status => CheckResult::failed(format!("Service status is {}", status.as_str_name())),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::transport::Server;
+
+    /// Starts a health server with `api` serving, `queue` not serving and
+    /// `cache` unknown.
+    async fn serve() -> String {
+        let (mut reporter, service) = tonic_health::server::health_reporter();
+        reporter
+            .set_service_status("api", tonic_health::ServingStatus::Serving)
+            .await;
+        reporter
+            .set_service_status("queue", tonic_health::ServingStatus::NotServing)
+            .await;
+        reporter
+            .set_service_status("cache", tonic_health::ServingStatus::Unknown)
+            .await;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            // keep the reporter alive, or the statuses are dropped
+            let _reporter = reporter;
+            Server::builder()
+                .add_service(service)
+                .serve_with_incoming(TcpListenerStream::new(listener))
+                .await
+                .unwrap();
+        });
+        addr
+    }
+
+    fn target(service: &str) -> GrpcTarget {
+        GrpcTarget {
+            service: service.to_string(),
+            ..GrpcTarget::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_serving_statuses() {
+        let addr = serve().await;
+        let timeout = Duration::from_secs(5);
+
+        let result = check(&addr, &target(""), timeout).await.unwrap();
+        assert_eq!(result.status, NodeStatus::Healthy);
+
+        let result = check(&addr, &target("api"), timeout).await.unwrap();
+        assert_eq!(result.status, NodeStatus::Healthy);
+
+        let result = check(&addr, &target("queue"), timeout).await.unwrap();
+        assert_eq!(result.status, NodeStatus::Down);
+        assert_eq!(result.error.unwrap(), "Service status is NOT_SERVING");
+
+        let result = check(&addr, &target("cache"), timeout).await.unwrap();
+        assert_eq!(result.status, NodeStatus::Degraded);
+
+        let result = check(&addr, &target("missing"), timeout).await.unwrap();
+        assert_eq!(result.status, NodeStatus::Down);
+        assert!(result
+            .error
+            .unwrap()
+            .starts_with("Health check failed: NotFound"));
+    }
+
+    #[tokio::test]
+    async fn test_refused() {
+        let result = check("127.0.0.1:1", &target(""), Duration::from_secs(5)).await;
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,23 @@
+pub mod database;
+pub mod dns;
+pub mod exec;
+pub mod grpc;
+pub mod redis;
+pub mod tcp;
+pub mod tls;
+
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+/// A probe that got no answer within its call timeout.
+#[derive(Debug)]
+pub struct TimedOut(pub Duration);
+
+impl fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Timed out after {}s", self.0.as_secs_f64())
+    }
+}
+
+impl Error for TimedOut {}
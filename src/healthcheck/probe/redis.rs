@@ -0,0 +1,365 @@
+use std::error::Error;
+use std::time::Duration;
+
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+use crate::healthcheck::node::model::CheckResult;
+use crate::healthcheck::probe::TimedOut;
+
+/// Longest reply read from the server; `INFO replication` is far shorter.
+const MAX_REPLY: usize = 64 * 1024;
+
+/// Replication role a Redis server is expected to have.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RedisRole {
+    Master,
+    Replica,
+}
+
+impl RedisRole {
+    pub fn parse(role: &str) -> Result<Self, String> {
+        match role {
+            "master" => Ok(RedisRole::Master),
+            // older servers call replicas slaves
+            "replica" | "slave" => Ok(RedisRole::Replica),
+            _ => Err(format!("Unknown Redis role: {}", role)),
+        }
+    }
+}
+
+/// How to log into a Redis server, and what to assert on its replication.
+#[derive(Debug, Clone, Default)]
+pub struct RedisCheck {
+    /// ACL user, for servers with more than the default user.
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub role: Option<RedisRole>,
+    /// Longest accepted replication lag in seconds: how long ago a replica
+    /// last heard from its master, or the lag of a master's replicas.
+    pub max_lag: Option<u64>,
+}
+
+/// Sends `PING` to the Redis server at `address`, authenticating first if
+/// there's a password, and checks `INFO replication` when a role or lag is
+/// asserted, all within `call_timeout`.
+pub async fn check(
+    address: &str,
+    redis: &RedisCheck,
+    call_timeout: Duration,
+) -> Result<CheckResult, Box<dyn Error + Send + Sync>> {
+    let error = match timeout(call_timeout, session(address, redis)).await {
+        Ok(result) => result?,
+        Err(_) => return Err(TimedOut(call_timeout).into()),
+    };
+
+    Ok(CheckResult::from_error(error))
+}
+
+async fn session(
+    address: &str,
+    redis: &RedisCheck,
+) -> Result<Option<String>, Box<dyn Error + Send + Sync>> {
+    let mut stream = BufReader::new(TcpStream::connect(address).await?);
+
+    if let Some(password) = &redis.password {
+        let mut auth = vec!["AUTH"];
+        if let Some(username) = &redis.username {
+            auth.push(username);
+        }
+        auth.push(password);
+        if let Reply::Error(error) = command(&mut stream, &auth).await? {
+            return Ok(Some(error));
+        }
+    }
+
+    match command(&mut stream, &["PING"]).await? {
+        Reply::Simple(pong) if pong == "PONG" => {}
+        Reply::Error(error) => return Ok(Some(error)),
+        reply => return Ok(Some(format!("Unexpected reply to PING: {:?}", reply))),
+    }
+
+    if redis.role.is_none() && redis.max_lag.is_none() {
+        return Ok(None);
+    }
+    let info = match command(&mut stream, &["INFO", "replication"]).await? {
+        Reply::Bulk(info) => info,
+        Reply::Error(error) => return Ok(Some(error)),
+        reply => return Ok(Some(format!("Unexpected reply to INFO: {:?}", reply))),
+    };
+
+    Ok(check_replication(&info, redis))
+}
+
+/// Compares the fields of `INFO replication` with the expected role and lag.
+fn check_replication(info: &str, redis: &RedisCheck) -> Option<String> {
+    let field = |name: &str| {
+        info.lines()
+            .find_map(|line| line.trim().strip_prefix(name)?.strip_prefix(':'))
+    };
+
+    let role = match field("role") {
+        Some("master") => RedisRole::Master,
+        Some("slave") => RedisRole::Replica,
+        role => return Some(format!("Unknown replication role: {:?}", role)),
+    };
+    if let Some(expected) = redis.role {
+        if role != expected {
+            return Some(format!("Role is {:?}, expected {:?}", role, expected));
+        }
+    }
+
+    let max_lag = redis.max_lag?;
+    match role {
+        RedisRole::Replica => {
+            if field("master_link_status") != Some("up") {
+                return Some("Replication link to the master is down".to_string());
+            }
+            let Some(lag) =
+                field("master_last_io_seconds_ago").and_then(|lag| lag.parse::<i64>().ok())
+            else {
+                return Some("Replication lag unavailable".to_string());
+            };
+            if lag > max_lag as i64 {
+                return Some(format!(
+                    "Replication lag is {}s, more than {}s",
+                    lag, max_lag
+                ));
+            }
+        }
+        // slave0:ip=10.0.0.2,port=6379,state=online,offset=1234,lag=0
+        RedisRole::Master => {
+            for line in info.lines().filter(|line| line.starts_with("slave")) {
+                let (name, details) = match line.trim().split_once(':') {
+                    Some(split) => split,
+                    None => continue,
+                };
+                if !name["slave".len()..].bytes().all(|b| b.is_ascii_digit()) {
+                    continue;
+                }
+                let lag = details
+                    .split(',')
+                    .find_map(|pair| pair.strip_prefix("lag="))
+                    .and_then(|lag| lag.parse::<u64>().ok());
+                let Some(lag) = lag else {
+                    return Some(format!("Replication lag of {} unavailable", name));
+                };
+                if lag > max_lag {
+                    return Some(format!(
+                        "Replication lag of {} is {}s, more than {}s",
+                        name, lag, max_lag
+                    ));
+                }
+            }
+        }
+    }
+
+    None
+}
+
+#[derive(Debug, PartialEq)]
+enum Reply {
+    Simple(String),
+    Error(String),
+    Integer(i64),
+    Bulk(String),
+    Nil,
+    Array(usize),
+}
+
+/// Sends a command as an array of bulk strings and reads its reply.
+async fn command(
+    stream: &mut BufReader<TcpStream>,
+    args: &[&str],
+) -> Result<Reply, Box<dyn Error + Send + Sync>> {
+    let mut request = format!("*{}\r\n", args.len());
+    for arg in args {
+        request += &format!("${}\r\n{}\r\n", arg.len(), arg);
+    }
+    stream.get_mut().write_all(request.as_bytes()).await?;
+
+    read_reply(stream).await
+}
+
+/// Reads one reply. Arrays only report their length, health checks never
+/// need their elements.
+async fn read_reply<R: AsyncBufRead + Unpin>(
+    stream: &mut R,
+) -> Result<Reply, Box<dyn Error + Send + Sync>> {
+    let mut line = String::new();
+    let mut limited = (&mut *stream).take(MAX_REPLY as u64);
+    if limited.read_line(&mut line).await? == 0 {
+        return Err("Connection closed by the server".into());
+    }
+    let line = line.trim_end_matches("\r\n");
+    let (kind, value) = line.split_at(line.len().min(1));
+
+    Ok(match kind {
+        "+" => Reply::Simple(value.to_string()),
+        "-" => Reply::Error(value.to_string()),
+        ":" => Reply::Integer(value.parse()?),
+        "*" => Reply::Array(value.parse::<i64>()?.max(0) as usize),
+        "$" => {
+            let len = value.parse::<i64>()?;
+            if len < 0 {
+                return Ok(Reply::Nil);
+            }
+            let len = len as usize;
+            if len > MAX_REPLY {
+                return Err(format!("Reply of {} bytes is too large", len).into());
+            }
+            let mut bulk = vec![0u8; len + 2];
+            stream.read_exact(&mut bulk).await?;
+            bulk.truncate(len);
+            Reply::Bulk(String::from_utf8_lossy(&bulk).to_string())
+        }
+        _ => return Err(format!("Malformed reply: {}", line).into()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::healthcheck::node::model::NodeStatus;
+    use tokio::net::TcpListener;
+
+    /// A Redis server requiring `password` and reporting `info` for
+    /// `INFO replication`.
+    async fn serve(password: &'static str, info: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    let mut stream = BufReader::new(stream);
+                    let mut authenticated = false;
+                    while let Ok(Reply::Array(len)) = read_reply(&mut stream).await {
+                        let mut args = Vec::with_capacity(len);
+                        for _ in 0..len {
+                            match read_reply(&mut stream).await.unwrap() {
+                                Reply::Bulk(arg) => args.push(arg),
+                                reply => panic!("unexpected {:?}", reply),
+                            }
+                        }
+                        let reply = match args[0].as_str() {
+                            "AUTH" if args.last().unwrap() == password => {
+                                authenticated = true;
+                                "+OK\r\n".to_string()
+                            }
+                            "AUTH" => "-WRONGPASS invalid username-password pair\r\n".to_string(),
+                            _ if !authenticated => {
+                                "-NOAUTH Authentication required.\r\n".to_string()
+                            }
+                            "PING" => "+PONG\r\n".to_string(),
+                            "INFO" => format!("${}\r\n{}\r\n", info.len(), info),
+                            _ => "-ERR unknown command\r\n".to_string(),
+                        };
+                        stream.get_mut().write_all(reply.as_bytes()).await.unwrap();
+                    }
+                });
+            }
+        });
+        addr
+    }
+
+    const REPLICA: &str = "# Replication\r\nrole:slave\r\nmaster_host:10.0.0.1\r\n\
+        master_link_status:up\r\nmaster_last_io_seconds_ago:4\r\n";
+    const MASTER: &str = "# Replication\r\nrole:master\r\nconnected_slaves:2\r\n\
+        slave0:ip=10.0.0.2,port=6379,state=online,offset=100,lag=0\r\n\
+        slave1:ip=10.0.0.3,port=6379,state=online,offset=90,lag=12\r\n";
+
+    fn redis(password: &str, role: Option<RedisRole>, max_lag: Option<u64>) -> RedisCheck {
+        RedisCheck {
+            username: None,
+            password: Some(password.to_string()),
+            role,
+            max_lag,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ping() {
+        let addr = serve("secret", REPLICA).await;
+
+        let result = check(&addr, &redis("secret", None, None), Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(result.status, NodeStatus::Healthy);
+
+        let result = check(&addr, &redis("wrong", None, None), Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(
+            result.error.unwrap(),
+            "WRONGPASS invalid username-password pair"
+        );
+
+        let anonymous = RedisCheck::default();
+        let result = check(&addr, &anonymous, Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(result.error.unwrap(), "NOAUTH Authentication required.");
+    }
+
+    #[tokio::test]
+    async fn test_replication() {
+        let replica = serve("secret", REPLICA).await;
+        let master = serve("secret", MASTER).await;
+        let timeout = Duration::from_secs(5);
+
+        let check_replica = redis("secret", Some(RedisRole::Replica), Some(5));
+        let result = check(&replica, &check_replica, timeout).await.unwrap();
+        assert_eq!(result.status, NodeStatus::Healthy);
+
+        let result = check(&replica, &redis("secret", None, Some(3)), timeout)
+            .await
+            .unwrap();
+        assert_eq!(result.error.unwrap(), "Replication lag is 4s, more than 3s");
+
+        let result = check(
+            &replica,
+            &redis("secret", Some(RedisRole::Master), None),
+            timeout,
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.error.unwrap(), "Role is Replica, expected Master");
+
+        let result = check(
+            &master,
+            &redis("secret", Some(RedisRole::Master), Some(10)),
+            timeout,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            result.error.unwrap(),
+            "Replication lag of slave1 is 12s, more than 10s"
+        );
+    }
+
+    #[test]
+    fn test_replica_link_down() {
+        let info = "role:slave\r\nmaster_link_status:down\r\nmaster_last_io_seconds_ago:-1\r\n";
+        let error = check_replication(info, &redis("", None, Some(10)));
+        assert_eq!(error.unwrap(), "Replication link to the master is down");
+    }
+
+    #[test]
+    fn test_lag_unavailable() {
+        let info = "role:slave\r\nmaster_link_status:up\r\n";
+        let error = check_replication(info, &redis("", None, Some(10)));
+        assert_eq!(error.unwrap(), "Replication lag unavailable");
+        let info = "role:slave\r\nmaster_link_status:up\r\nmaster_last_io_seconds_ago:?\r\n";
+        let error = check_replication(info, &redis("", None, Some(10)));
+        assert_eq!(error.unwrap(), "Replication lag unavailable");
+        // without a lag limit the lag isn't needed
+        assert_eq!(check_replication(info, &redis("", None, None)), None);
+
+        let info = "role:master\r\nslave0:ip=10.0.0.2,port=6379,state=online,offset=1234\r\n";
+        let error = check_replication(info, &redis("", None, Some(10)));
+        assert_eq!(error.unwrap(), "Replication lag of slave0 unavailable");
+    }
+}
@@ -0,0 +1,144 @@
+use std::error::Error;
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+use crate::healthcheck::node::model::CheckResult;
+use crate::healthcheck::probe::TimedOut;
+
+/// Longest response read while looking for the expected banner.
+const MAX_RESPONSE: usize = 4096;
+
+/// Connects to `address`, optionally writes `send` and waits for a response
+/// starting with `expect`, all within `call_timeout`.
+pub async fn check(
+    address: &str,
+    send: Option<&str>,
+    expect: Option<&str>,
+    call_timeout: Duration,
+) -> Result<CheckResult, Box<dyn Error + Send + Sync>> {
+    let error = match timeout(call_timeout, exchange(address, send, expect)).await {
+        Ok(result) => result?,
+        Err(_) => return Err(TimedOut(call_timeout).into()),
+    };
+
+    Ok(CheckResult::from_error(error))
+}
+
+async fn exchange(
+    address: &str,
+    send: Option<&str>,
+    expect: Option<&str>,
+) -> Result<Option<String>, Box<dyn Error + Send + Sync>> {
+    let mut stream = TcpStream::connect(address).await?;
+    if let Some(payload) = send {
+        stream.write_all(payload.as_bytes()).await?;
+    }
+
+    let expect = match expect {
+        Some(expect) => expect.as_bytes(),
+        None => return Ok(None),
+    };
+
+    let mut response = Vec::with_capacity(expect.len());
+    let mut buf = [0u8; 512];
+    while response.len() < expect.len().min(MAX_RESPONSE) {
+        let read = stream.read(&mut buf).await?;
+        if read == 0 {
+            break;
+        }
+        response.extend_from_slice(&buf[..read]);
+    }
+
+    if response.starts_with(expect) {
+        Ok(None)
+    } else {
+        Ok(Some(format!(
+            "Expected a response starting with '{}', got '{}'",
+            String::from_utf8_lossy(expect),
+            String::from_utf8_lossy(&response)
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::healthcheck::node::model::NodeStatus;
+    use tokio::net::TcpListener;
+
+    /// Accepts connections, reads what the client sends and answers with
+    /// `banner`.
+    async fn serve(banner: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    stream.write_all(banner.as_bytes()).await.unwrap();
+                    let mut buf = [0u8; 64];
+                    _ = stream.read(&mut buf).await;
+                });
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_connect() {
+        let addr = serve("").await;
+        let result = check(&addr, None, None, Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert_eq!(result.status, NodeStatus::Healthy);
+    }
+
+    #[tokio::test]
+    async fn test_connect_refused() {
+        let result = check("127.0.0.1:1", None, None, Duration::from_secs(1)).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_expect_banner() {
+        let addr = serve("+PONG\r\n").await;
+        let ok = check(
+            &addr,
+            Some("PING\r\n"),
+            Some("+PONG"),
+            Duration::from_secs(1),
+        )
+        .await
+        .unwrap();
+        assert_eq!(ok.status, NodeStatus::Healthy);
+
+        let wrong = check(
+            &addr,
+            Some("PING\r\n"),
+            Some("-ERR"),
+            Duration::from_secs(1),
+        )
+        .await
+        .unwrap();
+        assert_eq!(wrong.status, NodeStatus::Down);
+        assert!(wrong.error.unwrap().contains("+PONG"));
+    }
+
+    #[tokio::test]
+    async fn test_timeout() {
+        // the server never answers without a payload, so waiting for a banner
+        // runs into the timeout
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (_stream, _) = listener.accept().await.unwrap();
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        });
+
+        let result = check(&addr, None, Some("hello"), Duration::from_millis(200)).await;
+        assert!(result.unwrap_err().to_string().starts_with("Timed out"));
+    }
+}
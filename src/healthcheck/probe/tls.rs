@@ -0,0 +1,263 @@
+use std::error::Error;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use openssl::asn1::Asn1Time;
+use openssl::ssl::{SslConnector, SslMethod, SslVerifyMode};
+use openssl::x509::X509VerifyResult;
+use tokio::time::timeout;
+
+use crate::healthcheck::node::model::{CheckResult, NodeStatus};
+use crate::healthcheck::probe::TimedOut;
+
+/// Certificate requirements of a TLS node.
+#[derive(Debug, Clone)]
+pub struct CertPolicy {
+    /// Name the certificate has to be valid for, and sent as SNI.
+    pub server_name: String,
+    /// Extra root certificate file to trust, e.g. for an internal CA.
+    pub ca_file: Option<String>,
+    /// The node is down when the certificate expires within this many days.
+    pub min_days: i64,
+    /// The node is degraded when the certificate expires within this many days.
+    pub warn_days: Option<i64>,
+}
+
+/// Performs a TLS handshake with `address` and checks the leaf certificate's
+/// validity, hostname and remaining lifetime, all within `call_timeout`.
+pub async fn check(
+    address: &str,
+    policy: &CertPolicy,
+    call_timeout: Duration,
+) -> Result<CheckResult, Box<dyn Error + Send + Sync>> {
+    let address = address.to_string();
+    let policy = policy.clone();
+    let handshake = tokio::task::spawn_blocking(move || handshake(&address, &policy, call_timeout));
+    match timeout(call_timeout, handshake).await {
+        Ok(result) => result?,
+        Err(_) => Err(TimedOut(call_timeout).into()),
+    }
+}
+
+fn handshake(
+    address: &str,
+    policy: &CertPolicy,
+    call_timeout: Duration,
+) -> Result<CheckResult, Box<dyn Error + Send + Sync>> {
+    let addr = address
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| format!("Cannot resolve {}", address))?;
+    let stream = TcpStream::connect_timeout(&addr, call_timeout)?;
+    stream.set_read_timeout(Some(call_timeout))?;
+    stream.set_write_timeout(Some(call_timeout))?;
+
+    let mut connector = SslConnector::builder(SslMethod::tls())?;
+    if let Some(ca_file) = &policy.ca_file {
+        connector.set_ca_file(ca_file)?;
+    }
+    // finish the handshake whatever the verification outcome, so expiry can
+    // still be reported for untrusted certificates
+    connector.set_verify_callback(SslVerifyMode::PEER, |_, _| true);
+    let stream = connector
+        .build()
+        .connect(&policy.server_name, stream)
+        .map_err(|err| format!("TLS handshake failed: {}", err))?;
+
+    let ssl = stream.ssl();
+    let cert = ssl
+        .peer_certificate()
+        .ok_or("Server did not present a certificate")?;
+    let days_left = Asn1Time::days_from_now(0)?.diff(cert.not_after())?.days as i64;
+
+    let verify = ssl.verify_result();
+    let (status, error) = if verify != X509VerifyResult::OK {
+        (
+            NodeStatus::Down,
+            Some(format!(
+                "Certificate verification failed: {}",
+                verify.error_string()
+            )),
+        )
+    } else if days_left < policy.min_days {
+        (
+            NodeStatus::Down,
+            Some(format!("Certificate expires in {} days", days_left)),
+        )
+    } else if policy.warn_days.is_some_and(|warn| days_left < warn) {
+        (
+            NodeStatus::Degraded,
+            Some(format!("Certificate expires in {} days", days_left)),
+        )
+    } else {
+        (NodeStatus::Healthy, None)
+    };
+
+    Ok(CheckResult {
+        cert_days_left: Some(days_left),
+        ..CheckResult::new(status, error)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openssl::asn1::Asn1Time;
+    use openssl::hash::MessageDigest;
+    use openssl::pkey::PKey;
+    use openssl::rsa::Rsa;
+    use openssl::ssl::SslAcceptor;
+    use openssl::x509::extension::SubjectAlternativeName;
+    use openssl::x509::{X509NameBuilder, X509};
+    use std::net::TcpListener;
+    use std::path::PathBuf;
+    use std::thread;
+
+    /// A certificate written to a temporary file, removed once dropped.
+    struct CaFile(PathBuf);
+
+    impl CaFile {
+        fn path(&self) -> String {
+            self.0.to_string_lossy().to_string()
+        }
+    }
+
+    impl Drop for CaFile {
+        fn drop(&mut self) {
+            _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    /// Starts a TLS server with a self-signed certificate for `localhost`
+    /// valid for `days`, and returns its address and certificate file.
+    fn serve(days: u32) -> (String, CaFile) {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_text("CN", "localhost").unwrap();
+        let name = name.build();
+
+        let mut cert = X509::builder().unwrap();
+        cert.set_version(2).unwrap();
+        cert.set_subject_name(&name).unwrap();
+        cert.set_issuer_name(&name).unwrap();
+        cert.set_pubkey(&key).unwrap();
+        cert.set_not_before(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        cert.set_not_after(&Asn1Time::days_from_now(days).unwrap())
+            .unwrap();
+        let san = SubjectAlternativeName::new()
+            .dns("localhost")
+            .build(&cert.x509v3_context(None, None))
+            .unwrap();
+        cert.append_extension(san).unwrap();
+        cert.sign(&key, MessageDigest::sha256()).unwrap();
+        let cert = cert.build();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let ca_file =
+            std::env::temp_dir().join(format!("healthcheckify-{}.pem", address.replace(':', "-")));
+        std::fs::write(&ca_file, cert.to_pem().unwrap()).unwrap();
+
+        let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+        acceptor.set_private_key(&key).unwrap();
+        acceptor.set_certificate(&cert).unwrap();
+        let acceptor = acceptor.build();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                _ = acceptor.accept(stream);
+            }
+        });
+
+        (address, CaFile(ca_file))
+    }
+
+    fn policy(ca_file: Option<String>) -> CertPolicy {
+        CertPolicy {
+            server_name: "localhost".to_string(),
+            ca_file,
+            min_days: 7,
+            warn_days: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_trusted_certificate() {
+        let (address, ca_file) = serve(30);
+        let result = check(
+            &address,
+            &policy(Some(ca_file.path())),
+            Duration::from_secs(5),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result.status, NodeStatus::Healthy);
+        assert!(matches!(result.cert_days_left, Some(29..=30)));
+    }
+
+    #[tokio::test]
+    async fn test_untrusted_certificate() {
+        let (address, _ca_file) = serve(30);
+        let result = check(&address, &policy(None), Duration::from_secs(5))
+            .await
+            .unwrap();
+
+        assert_eq!(result.status, NodeStatus::Down);
+        assert!(result
+            .error
+            .unwrap()
+            .starts_with("Certificate verification failed"));
+        assert!(result.cert_days_left.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_hostname_mismatch() {
+        let (address, ca_file) = serve(30);
+        let mut policy = policy(Some(ca_file.path()));
+        policy.server_name = "example.com".to_string();
+        let result = check(&address, &policy, Duration::from_secs(5))
+            .await
+            .unwrap();
+
+        assert_eq!(result.status, NodeStatus::Down);
+        assert!(result
+            .error
+            .unwrap()
+            .contains("Certificate verification failed"));
+    }
+
+    #[tokio::test]
+    async fn test_expiry_thresholds() {
+        let (address, ca_file) = serve(10);
+        let mut policy = policy(Some(ca_file.path()));
+
+        policy.warn_days = Some(14);
+        let result = check(&address, &policy, Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(result.status, NodeStatus::Degraded);
+
+        policy.min_days = 14;
+        let result = check(&address, &policy, Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(result.status, NodeStatus::Down);
+        assert!(result.error.unwrap().starts_with("Certificate expires in"));
+    }
+
+    #[tokio::test]
+    async fn test_timeout() {
+        // accepts connections but never answers the handshake
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            let _streams: Vec<_> = listener.incoming().collect();
+        });
+
+        let started = std::time::Instant::now();
+        let result = check(&address, &policy(None), Duration::from_millis(300)).await;
+        assert!(result.unwrap_err().to_string().starts_with("Timed out"));
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,108 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use crate::healthcheck::node::{
+    history::Transition,
+    model::{Node, NodeStatus},
+};
+
+/// Structured status of one id and all of its services.
+#[derive(Debug, Serialize)]
+pub struct GroupReport {
+    pub id: String,
+    pub status: NodeStatus,
+    pub services: Vec<ServiceReport>,
+}
+
+/// Structured status of a single service, as of its most recent check.
+#[derive(Debug, Serialize)]
+pub struct ServiceReport {
+    pub url: String,
+    pub status: NodeStatus,
+    /// Unix timestamp, in seconds, of the last finished check.
+    pub last_check: Option<u64>,
+    pub latency_ms: Option<u64>,
+    pub http_code: Option<u16>,
+    /// Days until the TLS certificate expires, for TLS services.
+    pub cert_days_left: Option<i64>,
+    pub error: Option<String>,
+    /// First lines printed by the command, for exec services.
+    pub output: Option<String>,
+    /// Probes the last check took, retries included.
+    pub attempts: Option<u32>,
+    pub consecutive_failures: u32,
+    /// Whether the service changed status too often lately.
+    pub flapping: bool,
+    /// Latest status changes, oldest first.
+    pub transitions: Vec<TransitionReport>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TransitionReport {
+    /// Unix timestamp, in seconds.
+    pub at: u64,
+    pub from: NodeStatus,
+    pub to: NodeStatus,
+}
+
+impl TransitionReport {
+    fn new(transition: &Transition) -> Self {
+        Self {
+            at: unix_seconds(transition.at),
+            from: transition.from,
+            to: transition.to,
+        }
+    }
+}
+
+impl ServiceReport {
+    pub(crate) fn new(node: &Node) -> Self {
+        let last_result = node.last_result();
+
+        Self {
+            url: node.url().to_string(),
+            status: node.status(),
+            last_check: last_result.map(|result| unix_seconds(result.checked_at)),
+            latency_ms: last_result
+                .and_then(|result| result.latency)
+                .map(|latency| latency.as_millis() as u64),
+            http_code: last_result.and_then(|result| result.http_code),
+            cert_days_left: last_result.and_then(|result| result.cert_days_left),
+            error: last_result.and_then(|result| result.error.clone()),
+            output: last_result.and_then(|result| result.output.clone()),
+            attempts: last_result.map(|result| result.attempts),
+            consecutive_failures: node.consecutive_failures(),
+            flapping: node.is_flapping(),
+            transitions: node.transitions().map(TransitionReport::new).collect(),
+        }
+    }
+}
+
+/// State of the running config, as of the latest reload.
+#[derive(Debug, Serialize)]
+pub struct ReloadReport {
+    /// Unix timestamp, in seconds, of when the running config was loaded.
+    pub loaded_at: u64,
+    /// Unix timestamp, in seconds, of the latest rejected reload, if it came
+    /// after the running config was loaded.
+    pub failed_at: Option<u64>,
+    /// Why that reload was rejected.
+    pub error: Option<String>,
+}
+
+impl ReloadReport {
+    pub(crate) fn new(loaded_at: SystemTime, failure: Option<&(SystemTime, String)>) -> Self {
+        Self {
+            loaded_at: unix_seconds(loaded_at),
+            failed_at: failure.map(|(at, _)| unix_seconds(*at)),
+            error: failure.map(|(_, error)| error.clone()),
+        }
+    }
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
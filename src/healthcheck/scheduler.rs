@@ -0,0 +1,98 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::task::JoinHandle;
+
+use crate::{
+    healthcheck::health_checker::HealthChecker,
+    logger::log::{log, LogLevel},
+};
+
+/// How often the scheduler looks for nodes whose interval has elapsed.
+const TICK: Duration = Duration::from_secs(1);
+
+/// Drives every node on its own interval, independently of incoming HTTP
+/// requests. Each probe runs as its own task on the tokio runtime and only
+/// takes the checker lock to hand out work and to record the result.
+pub struct Scheduler {
+    health_checker: Arc<Mutex<HealthChecker>>,
+    client: reqwest::Client,
+}
+
+impl Scheduler {
+    pub fn new(health_checker: Arc<Mutex<HealthChecker>>) -> Self {
+        let client = health_checker.lock().unwrap().client();
+
+        Self {
+            health_checker,
+            client,
+        }
+    }
+
+    /// Spawns a probe task for every node that is due.
+    pub fn tick(&self) -> Vec<JoinHandle<()>> {
+        let probes = self.health_checker.lock().unwrap().due_probes();
+        probes
+            .into_iter()
+            .map(|(key, probe)| {
+                let health_checker = Arc::clone(&self.health_checker);
+                let client = self.client.clone();
+                tokio::spawn(async move {
+                    let result = probe.run(&client).await;
+                    health_checker.lock().unwrap().record(key, result);
+                })
+            })
+            .collect()
+    }
+
+    /// Keeps ticking the scheduler on the current runtime until the task is
+    /// aborted.
+    pub fn start(self) -> JoinHandle<()> {
+        log("Starting scheduler".to_string(), LogLevel::Info);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(TICK);
+            loop {
+                ticker.tick().await;
+                self.tick();
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::healthcheck::{node::model::NodeStatus, testing::serve};
+
+    #[tokio::test]
+    async fn test_tick_checks_due_nodes() {
+        let up = serve("HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 2\r\n\r\nok");
+        let data = format!(
+            r#"[
+            {{"id":"up","services":[{{"url":"{}"}}]}},
+            {{"id":"down","services":[{{"url":"http://127.0.0.1:1/"}}]}}
+            ]"#,
+            up
+        );
+        let checker = Arc::new(Mutex::new(HealthChecker::new(data).unwrap()));
+        let scheduler = Scheduler::new(Arc::clone(&checker));
+
+        assert_eq!(checker.lock().unwrap().status(0), NodeStatus::Processing);
+        for task in scheduler.tick() {
+            task.await.unwrap();
+        }
+
+        assert_eq!(checker.lock().unwrap().status(0), NodeStatus::Healthy);
+        assert_eq!(checker.lock().unwrap().status(1), NodeStatus::Down);
+    }
+
+    #[tokio::test]
+    async fn test_tick_skips_recent_nodes() {
+        let data = r#"[{"id":"test","services":[{"url":"http://127.0.0.1:1/"}]}]"#;
+        let checker = Arc::new(Mutex::new(HealthChecker::new(data.to_string()).unwrap()));
+        let scheduler = Scheduler::new(Arc::clone(&checker));
+
+        assert_eq!(scheduler.tick().len(), 1);
+        assert!(scheduler.tick().is_empty());
+    }
+}
@@ -0,0 +1,24 @@
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::thread;
+
+/// Starts an HTTP server on a random local port that answers every request
+/// with `response` verbatim, and returns its base url.
+pub(crate) fn serve(response: &'static str) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+            let mut buf = [0u8; 4096];
+            _ = stream.read(&mut buf);
+            _ = stream.write_all(response.as_bytes());
+        }
+    });
+
+    format!("http://{}/", addr)
+}
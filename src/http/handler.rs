@@ -1,25 +1,40 @@
 use std::sync::Mutex;
 
-use actix_web::{get, http::StatusCode, web, HttpResponse, Responder};
+use actix_web::{
+    get, http::header, http::StatusCode, post, web, HttpRequest, HttpResponse, Responder,
+};
+use serde::Deserialize;
 
 use crate::{
-    healthcheck::{health_checker::HealthChecker, node::model::NodeStatus},
+    healthcheck::{
+        health_checker::HealthChecker,
+        node::{heartbeat::Ping, model::NodeStatus},
+    },
+    http::metrics::render,
     logger::log::{log, LogLevel},
 };
 
+#[derive(Deserialize)]
+struct FormatQuery {
+    format: Option<String>,
+}
+
 #[get("/")]
-pub async fn home(health_checker: web::Data<Mutex<HealthChecker>>) -> impl Responder {
+pub async fn home(
+    req: HttpRequest,
+    health_checker: web::Data<Mutex<HealthChecker>>,
+) -> impl Responder {
+    let health_checker = health_checker.lock().unwrap();
+    if wants_json(&req) {
+        return HttpResponse::Ok().json(health_checker.report());
+    }
+
     let mut response = String::from("");
-    health_checker.lock().unwrap().check_all();
-    let ids = health_checker.lock().unwrap().get_node_ids();
-    for node_id in ids {
+    for node_id in health_checker.get_node_ids() {
         response += &format!("{}: ", node_id);
-        let stat = health_checker.lock().unwrap().status_by_id(&node_id);
-        let answer = match stat {
-            Some(stat) if stat == NodeStatus::Down => format!("{}\n", "down"),
-            Some(stat) if stat == NodeStatus::Healthy => format!("{}\n", "healthy"),
-            Some(stat) if stat == NodeStatus::Processing => format!("{}\n", "processing"),
-            _ => format!("{}\n", "error"),
+        let answer = match health_checker.status_by_id(&node_id) {
+            Some(stat) => format!("{}\n", stat.as_str()),
+            None => format!("{}\n", "error"),
         };
         response += &answer;
     }
@@ -29,22 +44,314 @@ pub async fn home(health_checker: web::Data<Mutex<HealthChecker>>) -> impl Respo
 
 #[get("/{service_id}")]
 pub async fn service_status(
+    req: HttpRequest,
     path: web::Path<String>,
     health_checker: web::Data<Mutex<HealthChecker>>,
 ) -> impl Responder {
     log(format!("Request for service: {}", path), LogLevel::Info);
     let node_id = path.into_inner();
-    let stat = health_checker.lock().unwrap().check_by_id(node_id.as_str());
+    let health_checker = health_checker.lock().unwrap();
+    let stat = health_checker.status_by_id(node_id.as_str());
     log(format!("Status: {:?}", stat), LogLevel::Info);
-    match stat {
-        Ok(stat) if stat == NodeStatus::Down => get_response("error", 500),
-        Ok(stat) if stat == NodeStatus::Healthy => get_response("ok", 200),
-        Ok(stat) if stat == NodeStatus::Processing => get_response("ok", 200),
-        Ok(_) => get_response("not found", 404),
-        Err(_) => get_response("not found", 404),
+    let (message, response_code) = match stat {
+        Some(NodeStatus::Down) => ("error", 500),
+        Some(NodeStatus::Healthy) => ("ok", 200),
+        Some(NodeStatus::Degraded) => (
+            "degraded",
+            health_checker.degraded_code_by_id(&node_id).unwrap_or(200),
+        ),
+        Some(NodeStatus::Processing) => ("ok", 200),
+        None if wants_json(&req) => {
+            return HttpResponse::NotFound().json(serde_json::json!({ "error": "not found" }))
+        }
+        None => return get_response("not found", 404),
+    };
+
+    if wants_json(&req) {
+        return HttpResponse::build(StatusCode::from_u16(response_code).unwrap())
+            .json(health_checker.report_by_id(&node_id));
+    }
+
+    // list the member services that are down, even if the group as a whole
+    // is still considered healthy by its policy
+    let mut response = String::from(message);
+    for url in health_checker.failed_by_id(&node_id).unwrap_or_default() {
+        response += &format!("\nfailed: {}", url);
+    }
+    for url in health_checker.degraded_by_id(&node_id).unwrap_or_default() {
+        response += &format!("\ndegraded: {}", url);
+    }
+    for url in health_checker.flapping_by_id(&node_id).unwrap_or_default() {
+        response += &format!("\nflapping: {}", url);
+    }
+
+    get_response(&response, response_code)
+}
+
+/// State of the running config: `ok`, or the errors of the latest reload
+/// if it was rejected.
+#[get("/config/status")]
+pub async fn config_status(
+    req: HttpRequest,
+    health_checker: web::Data<Mutex<HealthChecker>>,
+) -> impl Responder {
+    let report = health_checker.lock().unwrap().reload_report();
+    let response_code = match report.error {
+        Some(_) => 500,
+        None => 200,
+    };
+
+    if wants_json(&req) {
+        return HttpResponse::build(StatusCode::from_u16(response_code).unwrap()).json(report);
+    }
+    match report.error {
+        Some(error) => get_response(&format!("error\n{}", error), response_code),
+        None => get_response("ok", response_code),
     }
 }
 
+/// Ping of a job monitored by a heartbeat service: it ran successfully. The
+/// body, if any, is kept as the service's output.
+#[post("/heartbeat/{service_id}")]
+pub async fn heartbeat(
+    path: web::Path<String>,
+    body: String,
+    health_checker: web::Data<Mutex<HealthChecker>>,
+) -> impl Responder {
+    record_ping(&path.into_inner(), Ping::Success, &body, &health_checker)
+}
+
+/// Ping of a job monitored by a heartbeat service, with an explicit
+/// `start`, `success` or `fail` signal.
+#[post("/heartbeat/{service_id}/{signal}")]
+pub async fn heartbeat_signal(
+    path: web::Path<(String, String)>,
+    body: String,
+    health_checker: web::Data<Mutex<HealthChecker>>,
+) -> impl Responder {
+    let (node_id, signal) = path.into_inner();
+    match Ping::parse(&signal) {
+        Some(ping) => record_ping(&node_id, ping, &body, &health_checker),
+        None => get_response("unknown signal", 404),
+    }
+}
+
+fn record_ping(
+    node_id: &str,
+    ping: Ping,
+    body: &str,
+    health_checker: &Mutex<HealthChecker>,
+) -> HttpResponse {
+    log(
+        format!("Ping for service: {} {:?}", node_id, ping),
+        LogLevel::Info,
+    );
+    let message = Some(body).filter(|body| !body.trim().is_empty());
+    if health_checker.lock().unwrap().ping(node_id, ping, message) {
+        get_response("ok", 200)
+    } else {
+        get_response("not found", 404)
+    }
+}
+
+/// Prometheus scrape endpoint. Not bound with a route macro since its path
+/// is configurable; it has to be registered before `service_status`.
+pub async fn metrics(health_checker: web::Data<Mutex<HealthChecker>>) -> impl Responder {
+    let body = render(&health_checker.lock().unwrap());
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(body)
+}
+
+/// JSON is returned for `?format=json` or when the client only accepts JSON,
+/// plain text otherwise.
+fn wants_json(req: &HttpRequest) -> bool {
+    if let Ok(query) = web::Query::<FormatQuery>::from_query(req.query_string()) {
+        if let Some(format) = &query.format {
+            return format.eq_ignore_ascii_case("json");
+        }
+    }
+
+    req.headers()
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .map(|accept| accept.contains("application/json") && !accept.contains("text/plain"))
+        .unwrap_or(false)
+}
+
 fn get_response(message: &str, response_code: u16) -> HttpResponse {
     HttpResponse::build(StatusCode::from_u16(response_code).unwrap()).body(message.to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::healthcheck::parser::ConfigFormat;
+    use actix_web::{test, App};
+
+    fn app_data() -> web::Data<Mutex<HealthChecker>> {
+        let data = r#"[{"id":"test","services":[{"url":"http://127.0.0.1:1/"}]}]"#;
+        web::Data::new(Mutex::new(HealthChecker::new(data.to_string()).unwrap()))
+    }
+
+    #[actix_web::test]
+    async fn test_home_formats() {
+        let app = test::init_service(
+            App::new()
+                .app_data(app_data())
+                .service(home)
+                .service(service_status),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/").to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        assert_eq!(body, "test: processing\n");
+
+        let req = test::TestRequest::get().uri("/?format=json").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body[0]["id"], "test");
+        assert_eq!(body[0]["status"], "processing");
+        assert_eq!(body[0]["services"][0]["url"], "http://127.0.0.1:1/");
+        assert_eq!(body[0]["services"][0]["consecutive_failures"], 0);
+    }
+
+    #[actix_web::test]
+    async fn test_service_status_json() {
+        let app = test::init_service(
+            App::new()
+                .app_data(app_data())
+                .service(home)
+                .service(service_status),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/test")
+            .insert_header((header::ACCEPT, "application/json"))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["id"], "test");
+        assert_eq!(body["services"][0]["last_check"], serde_json::Value::Null);
+
+        let req = test::TestRequest::get()
+            .uri("/missing?format=json")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn test_degraded_code() {
+        let data = r#"[{"id":"batch","degraded_code":429,"services":[
+            {"type":"exec","command":"sh","args":["-c","exit 1"]}
+        ]}]"#;
+        let mut health_checker = HealthChecker::new(data.to_string()).unwrap();
+        health_checker.check_all().await;
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(Mutex::new(health_checker)))
+                .service(service_status),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/batch").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        let body = test::read_body(resp).await;
+        assert!(body.starts_with(b"degraded\ndegraded: sh"));
+    }
+
+    #[actix_web::test]
+    async fn test_heartbeat_routes() {
+        let data = r#"[{"id":"nightly","services":[{"type":"heartbeat","interval":86400}]}]"#;
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(Mutex::new(
+                    HealthChecker::new(data.to_string()).unwrap(),
+                )))
+                .service(heartbeat)
+                .service(heartbeat_signal)
+                .service(service_status),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/heartbeat/nightly/start")
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+        let req = test::TestRequest::post()
+            .uri("/heartbeat/nightly/fail")
+            .set_payload("exit code 3")
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+        let req = test::TestRequest::get().uri("/nightly").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let req = test::TestRequest::post()
+            .uri("/heartbeat/nightly")
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+        let req = test::TestRequest::get().uri("/nightly").to_request();
+        assert_eq!(test::call_and_read_body(&app, req).await, "ok");
+
+        let req = test::TestRequest::post()
+            .uri("/heartbeat/nightly/restart")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let req = test::TestRequest::post()
+            .uri("/heartbeat/missing")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn test_config_status() {
+        let health_checker = app_data();
+        let app = test::init_service(
+            App::new()
+                .app_data(health_checker.clone())
+                .service(config_status)
+                .service(service_status),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/config/status").to_request();
+        assert_eq!(test::call_and_read_body(&app, req).await, "ok");
+
+        _ = health_checker
+            .lock()
+            .unwrap()
+            .reload(r#"[{"id":"test"}]"#, ConfigFormat::Json);
+        let req = test::TestRequest::get()
+            .uri("/config/status?format=json")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["error"], "[0]: missing field `services`");
+        assert!(body["failed_at"].is_u64());
+
+        let req = test::TestRequest::get().uri("/test").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn test_metrics_route() {
+        let app = test::init_service(
+            App::new()
+                .app_data(app_data())
+                .route("/metrics", web::get().to(metrics))
+                .service(service_status),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/metrics").to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        let body = std::str::from_utf8(&body).unwrap();
+        assert!(body.contains("healthcheckify_checks_total{id=\"test\""));
+    }
+}
@@ -0,0 +1,290 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::time::UNIX_EPOCH;
+
+use crate::healthcheck::{
+    health_checker::HealthChecker,
+    node::{
+        metrics::DURATION_BUCKETS,
+        model::{Node, NodeStatus},
+    },
+};
+
+/// Where metrics are served unless `METRICS_PATH` says otherwise.
+pub const DEFAULT_METRICS_PATH: &str = "/metrics";
+
+/// Checks that a metrics path is absolute and does not shadow one of the
+/// built-in routes.
+pub fn check_path(path: &str) -> Result<(), String> {
+    if !path.starts_with('/') {
+        return Err(String::from("must start with '/'"));
+    }
+    if path == "/" || path == "/config/status" || path.starts_with("/heartbeat/") {
+        return Err(String::from("collides with a built-in route"));
+    }
+    Ok(())
+}
+
+const STATUSES: [NodeStatus; 4] = [
+    NodeStatus::Healthy,
+    NodeStatus::Degraded,
+    NodeStatus::Processing,
+    NodeStatus::Down,
+];
+
+/// Renders the metrics of every node in the Prometheus text exposition
+/// format, labelled by node id, service index and url.
+pub fn render(health_checker: &HealthChecker) -> String {
+    let mut out = String::new();
+    let nodes = health_checker.nodes();
+    let labels = node_labels(nodes);
+
+    header(
+        &mut out,
+        "healthcheckify_node_status",
+        "gauge",
+        "Current status of the node, 1 for the active status.",
+    );
+    for (node, labels) in nodes.iter().zip(&labels) {
+        for status in STATUSES {
+            let value = (node.status() == status) as u8;
+            let labels = format!("{},status=\"{}\"", labels, status.as_str());
+            _ = writeln!(out, "healthcheckify_node_status{{{}}} {}", labels, value);
+        }
+    }
+
+    header(
+        &mut out,
+        "healthcheckify_probe_duration_seconds",
+        "histogram",
+        "Duration of health check probes.",
+    );
+    for (node, labels) in nodes.iter().zip(&labels) {
+        let metrics = node.metrics();
+        for (bound, count) in DURATION_BUCKETS.iter().zip(metrics.duration_buckets) {
+            _ = writeln!(
+                out,
+                "healthcheckify_probe_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                labels, bound, count
+            );
+        }
+        _ = writeln!(
+            out,
+            "healthcheckify_probe_duration_seconds_bucket{{{},le=\"+Inf\"}} {}",
+            labels, metrics.duration_count
+        );
+        _ = writeln!(
+            out,
+            "healthcheckify_probe_duration_seconds_sum{{{}}} {}",
+            labels, metrics.duration_sum
+        );
+        _ = writeln!(
+            out,
+            "healthcheckify_probe_duration_seconds_count{{{}}} {}",
+            labels, metrics.duration_count
+        );
+    }
+
+    header(
+        &mut out,
+        "healthcheckify_job_duration_seconds",
+        "gauge",
+        "Duration of the last job reported to a heartbeat service.",
+    );
+    for (node, labels) in nodes.iter().zip(&labels) {
+        if let Some(duration) = node.metrics().job_duration {
+            _ = writeln!(
+                out,
+                "healthcheckify_job_duration_seconds{{{}}} {}",
+                labels, duration
+            );
+        }
+    }
+
+    header(
+        &mut out,
+        "healthcheckify_last_check_timestamp_seconds",
+        "gauge",
+        "Unix time of the last finished check.",
+    );
+    for (node, labels) in nodes.iter().zip(&labels) {
+        if let Some(result) = node.last_result() {
+            let timestamp = result
+                .checked_at
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_secs_f64())
+                .unwrap_or(0.0);
+            _ = writeln!(
+                out,
+                "healthcheckify_last_check_timestamp_seconds{{{}}} {}",
+                labels, timestamp
+            );
+        }
+    }
+
+    header(
+        &mut out,
+        "healthcheckify_checks_total",
+        "counter",
+        "Number of checks run against the node.",
+    );
+    for (node, labels) in nodes.iter().zip(&labels) {
+        _ = writeln!(
+            out,
+            "healthcheckify_checks_total{{{}}} {}",
+            labels,
+            node.metrics().checks_total
+        );
+    }
+
+    header(
+        &mut out,
+        "healthcheckify_check_failures_total",
+        "counter",
+        "Number of checks that found the node down.",
+    );
+    for (node, labels) in nodes.iter().zip(&labels) {
+        _ = writeln!(
+            out,
+            "healthcheckify_check_failures_total{{{}}} {}",
+            labels,
+            node.metrics().failures_total
+        );
+    }
+
+    header(
+        &mut out,
+        "healthcheckify_node_flapping",
+        "gauge",
+        "1 if the node changed status too often lately.",
+    );
+    for (node, labels) in nodes.iter().zip(&labels) {
+        _ = writeln!(
+            out,
+            "healthcheckify_node_flapping{{{}}} {}",
+            labels,
+            node.is_flapping() as u8
+        );
+    }
+
+    out
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    _ = writeln!(out, "# HELP {} {}", name, help);
+    _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// The labels of every node: its id, url and position among the services of
+/// its id, since services of an id may share a url.
+fn node_labels(nodes: &[Node]) -> Vec<String> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    nodes
+        .iter()
+        .map(|node| {
+            let service = counts.entry(&node.id).or_default();
+            let labels = format!(
+                "id=\"{}\",service=\"{}\",url=\"{}\"",
+                escape(&node.id),
+                service,
+                escape(node.url())
+            );
+            *service += 1;
+            labels
+        })
+        .collect()
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::healthcheck::node::heartbeat::Ping;
+
+    #[tokio::test]
+    async fn test_render() {
+        let data = r#"[{"id":"test","services":[{"url":"http://127.0.0.1:1/"}]}]"#;
+        let mut checker = HealthChecker::new(data.to_string()).unwrap();
+        checker.check_all().await;
+
+        let out = render(&checker);
+        let labels = r#"id="test",service="0",url="http://127.0.0.1:1/""#;
+        assert!(out.contains(&format!(
+            "healthcheckify_node_status{{{},status=\"down\"}} 1",
+            labels
+        )));
+        assert!(out.contains(&format!(
+            "healthcheckify_node_status{{{},status=\"healthy\"}} 0",
+            labels
+        )));
+        assert!(out.contains(&format!("healthcheckify_checks_total{{{}}} 1", labels)));
+        assert!(out.contains(&format!(
+            "healthcheckify_check_failures_total{{{}}} 1",
+            labels
+        )));
+        assert!(out.contains(&format!(
+            "healthcheckify_probe_duration_seconds_count{{{}}} 1",
+            labels
+        )));
+        assert!(out.contains("# TYPE healthcheckify_probe_duration_seconds histogram"));
+        assert!(out.contains(&format!("healthcheckify_node_flapping{{{}}} 0", labels)));
+    }
+
+    #[tokio::test]
+    async fn test_render_heartbeat() {
+        let data = r#"[{"id":"backup","services":[{"type":"heartbeat","interval":3600}]}]"#;
+        let mut checker = HealthChecker::new(data.to_string()).unwrap();
+        checker.ping("backup", Ping::Start, None);
+        checker.ping("backup", Ping::Success, None);
+
+        let out = render(&checker);
+        let labels = r#"id="backup",service="0",url="/heartbeat/backup""#;
+        assert!(out.contains(&format!(
+            "healthcheckify_probe_duration_seconds_count{{{}}} 0",
+            labels
+        )));
+        assert!(out.contains(&format!(
+            "healthcheckify_job_duration_seconds{{{}}} ",
+            labels
+        )));
+        assert!(out.contains(&format!("healthcheckify_checks_total{{{}}} 1", labels)));
+    }
+
+    #[test]
+    fn test_render_shared_url() {
+        let data = r#"[{"id":"backup","services":[
+            {"type":"heartbeat","interval":3600},
+            {"type":"heartbeat","interval":60}
+        ]}]"#;
+        let checker = HealthChecker::new(data.to_string()).unwrap();
+
+        let out = render(&checker);
+        for service in ["0", "1"] {
+            assert!(out.contains(&format!(
+                "healthcheckify_checks_total{{id=\"backup\",service=\"{}\",url=\"/heartbeat/backup\"}} 0",
+                service
+            )));
+        }
+    }
+
+    #[test]
+    fn test_check_path() {
+        assert!(check_path("/metrics").is_ok());
+        assert!(check_path("/heartbeat").is_ok());
+        assert!(check_path("metrics").is_err());
+        assert!(check_path("/").is_err());
+        assert!(check_path("/config/status").is_err());
+        assert!(check_path("/heartbeat/backup").is_err());
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+}
@@ -1,2 +1,3 @@
 pub mod handler;
 pub mod listener;
+pub mod metrics;
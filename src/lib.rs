@@ -1,5 +1,6 @@
+pub mod cli;
 pub mod config;
+pub mod grpc;
 pub mod healthcheck;
 pub mod http;
 pub mod logger;
-pub mod thread;
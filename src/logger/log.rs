@@ -1,4 +1,6 @@
 use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
 
 pub enum LogLevel {
     Debug,
@@ -7,8 +9,28 @@ pub enum LogLevel {
     Error,
 }
 
+/// Level set on the command line, which takes precedence over `LOG_LEVEL`.
+static LEVEL: OnceLock<String> = OnceLock::new();
+
+/// Overrides `LOG_LEVEL` for the rest of the run.
+pub fn set_log_level(level: &str) {
+    _ = LEVEL.set(level.to_string());
+}
+
+/// Whether logs go to stderr rather than stdout.
+static STDERR: AtomicBool = AtomicBool::new(false);
+
+/// Sends logs to stderr for the rest of the run, keeping stdout for the
+/// output of a command.
+pub fn log_to_stderr() {
+    STDERR.store(true, Ordering::Relaxed);
+}
+
 pub fn log(log: String, _log_level: LogLevel) {
-    let log_level = env::var("LOG_LEVEL").unwrap_or("error".to_string());
+    let log_level = match LEVEL.get() {
+        Some(level) => level.clone(),
+        None => env::var("LOG_LEVEL").unwrap_or("error".to_string()),
+    };
     let log_level = log_level.to_lowercase();
 
     let log_level = match log_level.as_str() {
@@ -20,6 +42,9 @@ pub fn log(log: String, _log_level: LogLevel) {
     };
 
     if log_level as u8 <= _log_level as u8 {
-        println!("{}", log);
+        match STDERR.load(Ordering::Relaxed) {
+            true => eprintln!("{}", log),
+            false => println!("{}", log),
+        }
     }
 }
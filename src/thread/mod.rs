@@ -1,2 +0,0 @@
-pub mod threadpool;
-mod worker;